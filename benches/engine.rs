@@ -0,0 +1,73 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Benchmarks for the engine's hot paths, so that changes
+//! to how numbers are represented can be judged by
+//! measurement. Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use net_15::solve::Solver;
+use net_15::{Numbers, Strategy, TARGET};
+
+/// Positions to measure on, as a name and the pool and
+/// hands of the player to move and the other player.
+const POSITIONS: [(&str, &str, &str, &str); 3] = [
+    ("opening", "123456789", "", ""),
+    ("middlegame", "13789", "24", "56"),
+    ("endgame", "137", "248", "569"),
+];
+
+/// A position from its digits.
+fn position(board: &str, own: &str, other: &str) -> (Numbers, Numbers, Numbers) {
+    let numbers = |digits| Numbers::from_digits(digits).unwrap();
+    (numbers(board), numbers(own), numbers(other))
+}
+
+fn won(c: &mut Criterion) {
+    let mut group = c.benchmark_group("won");
+    for (name, hand) in [("win", "2469"), ("no win", "1237"), ("empty", "")] {
+        let hand = Numbers::from_digits(hand).unwrap();
+        group.bench_function(name, |b| b.iter(|| black_box(&hand).won(TARGET)));
+    }
+    group.finish();
+}
+
+fn moves(c: &mut Criterion) {
+    let mut group = c.benchmark_group("moves");
+    for (name, board, own, other) in POSITIONS {
+        let (board, own, other) = position(board, own, other);
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(&board).tactical_choice(&own, &[&other], TARGET))
+        });
+    }
+    group.finish();
+}
+
+fn heuristic(c: &mut Criterion) {
+    let mut group = c.benchmark_group("heuristic");
+    for (name, board, _, _) in POSITIONS {
+        let board = Numbers::from_digits(board).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(&board).heuristic_choice(Strategy::default(), false, TARGET))
+        });
+    }
+    group.finish();
+}
+
+/// The machine solves afresh for every move, so each
+/// iteration starts with an empty memo table.
+fn solver(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solver");
+    for (name, board, own, other) in POSITIONS {
+        let (board, own, other) = position(board, own, other);
+        group.bench_function(name, |b| {
+            b.iter(|| Solver::new(TARGET).best_moves(black_box(&board), &own, &other))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, won, moves, heuristic, solver);
+criterion_main!(benches);
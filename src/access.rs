@@ -0,0 +1,172 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Access log: one line per client connection, written when
+//! the connection ends, to a file that is rotated by size.
+//!
+//! Each line holds the connect time in seconds since the
+//! Unix epoch, the peer address, how long the connection
+//! lasted, the number of games finished, the bytes read from
+//! and written to the client and why the connection ended.
+
+use crate::traffic::{Counted, Traffic};
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Number of rotated logs kept besides the current one.
+const KEEP: usize = 3;
+
+/// An access log file, rotated once it grows past a size
+/// limit. Old logs are renamed `<path>.1`, `<path>.2` and
+/// so on, oldest last.
+pub struct AccessLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+/// Open `path` for appending.
+fn append(path: &Path) -> Result<File, io::Error> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Name of the `n`th rotated log.
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+impl AccessLog {
+    /// Open the access log at `path`, rotating whenever it
+    /// would grow past `max_size` bytes.
+    pub fn open<P: Into<PathBuf>>(path: P, max_size: u64) -> Result<AccessLog, io::Error> {
+        let path = path.into();
+        let file = append(&path)?;
+        let size = file.metadata()?.len();
+        Ok(AccessLog {
+            path,
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    /// Append a line to the log, rotating first if needed.
+    pub fn write(&mut self, line: &str) -> Result<(), io::Error> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Shift the old logs along and start a new one.
+    fn rotate(&mut self) -> Result<(), io::Error> {
+        for n in (1..KEEP).rev() {
+            let from = rotated(&self.path, n);
+            if from.exists() {
+                fs::rename(from, rotated(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(&self.path, 1))?;
+        self.file = append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// One client connection, logged when dropped. Dropping is
+/// the only way a connection's thread can end, so the entry
+/// is written on every path out, panics included.
+pub struct Visit {
+    log: Option<Arc<Mutex<AccessLog>>>,
+    peer: SocketAddr,
+    connected: SystemTime,
+    start: Instant,
+    /// Games finished so far.
+    pub games: usize,
+    /// Bytes moved over the connection.
+    traffic: Arc<Traffic>,
+    /// Why the connection ended.
+    reason: String,
+}
+
+impl Visit {
+    /// Start tracking a connection from `peer`, to be
+    /// logged to `log` if there is one. The connection may
+    /// move up to `limit` bytes, if given.
+    pub fn new(log: Option<Arc<Mutex<AccessLog>>>, peer: SocketAddr, limit: Option<u64>) -> Visit {
+        Visit {
+            log,
+            peer,
+            connected: SystemTime::now(),
+            start: Instant::now(),
+            games: 0,
+            traffic: Arc::new(Traffic::new(limit)),
+            reason: "aborted".to_string(),
+        }
+    }
+
+    /// Wrap a stream of the connection so that its traffic
+    /// is counted.
+    pub fn count<T>(&self, stream: T) -> Counted<T> {
+        Counted::new(stream, Arc::clone(&self.traffic))
+    }
+
+    /// Bytes moved over the connection so far.
+    pub fn traffic(&self) -> &Traffic {
+        &self.traffic
+    }
+
+    /// Address of the client.
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Record why the connection ended. Connections that
+    /// never get here are logged as aborted.
+    pub fn ended(&mut self, reason: &str) {
+        self.reason = reason.to_string();
+    }
+}
+
+impl Drop for Visit {
+    fn drop(&mut self) {
+        let log = match self.log {
+            Some(ref log) => log,
+            None => return,
+        };
+        let connected = self
+            .connected
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_secs())
+            .unwrap_or(0);
+        let line = format!(
+            "{} {} {:.1}s {} {} {} {}",
+            connected,
+            self.peer,
+            self.start.elapsed().as_secs_f64(),
+            self.games,
+            self.traffic.read(),
+            self.traffic.written(),
+            self.reason,
+        );
+        // A panic elsewhere may have poisoned the lock; the
+        // log itself is still fine to write to.
+        let mut log = log.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = log.write(&line) {
+            eprintln!("couldn't write access log: {}", e);
+        }
+    }
+}
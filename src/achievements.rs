@@ -0,0 +1,91 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Achievements a client can earn over a session.
+//!
+//! Achievements are judged from how each of the client's
+//! games ended, as the session plays them. Clients are
+//! anonymous, so achievements last as long as the
+//! connection.
+
+/// Every achievement, as a name and how to earn it.
+const ACHIEVEMENTS: [(&str, &str); 5] = [
+    ("first fifteen", "win a game"),
+    ("quick fifteen", "win with three numbers"),
+    ("stalemate", "draw against perfect play"),
+    ("giant killer", "beat perfect play"),
+    ("flawless series", "win three games in a row"),
+];
+
+/// Wins in a row needed for a flawless series.
+const SERIES: usize = 3;
+
+/// How one of the client's games ended.
+#[derive(Clone, Copy)]
+pub enum Ending {
+    /// The client won, holding this many numbers.
+    Won(usize),
+    Lost,
+    Drawn,
+}
+
+/// Achievements earned so far, and what is needed to judge
+/// the rest.
+#[derive(Default)]
+pub struct Achievements {
+    /// Indices into `ACHIEVEMENTS` of the ones earned, in
+    /// the order they were earned.
+    earned: Vec<usize>,
+    /// Games won in a row.
+    streak: usize,
+}
+
+impl Achievements {
+    /// Start a session with nothing earned.
+    pub fn new() -> Achievements {
+        Achievements::default()
+    }
+
+    /// Judge a finished game that ended in `ending`, with
+    /// the machine having played perfectly throughout if
+    /// `perfect`. Returns the achievements it earned, as
+    /// lines ready to show.
+    pub fn judge(&mut self, ending: Ending, perfect: bool) -> Vec<String> {
+        let won = matches!(ending, Ending::Won(_));
+        self.streak = if won { self.streak + 1 } else { 0 };
+        // In the same order as `ACHIEVEMENTS`.
+        let checks = [
+            won,
+            matches!(ending, Ending::Won(3)),
+            matches!(ending, Ending::Drawn) && perfect,
+            won && perfect,
+            self.streak >= SERIES,
+        ];
+        let mut lines = Vec::new();
+        for (i, _) in checks.into_iter().enumerate().filter(|&(_, c)| c) {
+            if !self.earned.contains(&i) {
+                self.earned.push(i);
+                let (name, how) = ACHIEVEMENTS[i];
+                lines.push(format!("achievement: {} ({})", name, how));
+            }
+        }
+        lines
+    }
+
+    /// List the achievements, earned ones first.
+    pub fn list(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .earned
+            .iter()
+            .map(|&i| format!("* {} ({})", ACHIEVEMENTS[i].0, ACHIEVEMENTS[i].1))
+            .collect();
+        for (i, (name, how)) in ACHIEVEMENTS.iter().enumerate() {
+            if !self.earned.contains(&i) {
+                lines.push(format!("  {} ({})", name, how));
+            }
+        }
+        lines
+    }
+}
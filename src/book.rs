@@ -0,0 +1,91 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Opening book for the perfect strategy.
+//!
+//! Perfect play alone is dull: with every move scored only
+//! as a win, draw or loss, the machine would play the same
+//! few lines over and over. The book lists preferred moves
+//! for early positions with relative weights, and the
+//! machine picks among those the solver agrees are best.
+//! See `book.txt` for the format.
+
+use crate::learning::position;
+use crate::Numbers;
+
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use std::collections::HashMap;
+
+/// Candidate moves with weights, indexed by position.
+pub struct Book {
+    entries: HashMap<u32, Vec<(u64, u32)>>,
+}
+
+impl Book {
+    /// The book for the standard game.
+    pub fn standard() -> Book {
+        Book::parse(include_str!("book.txt")).unwrap()
+    }
+
+    /// Parse a book from its text form.
+    pub fn parse(text: &str) -> Result<Book, String> {
+        let mut entries = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |msg| format!("book line {}: {}", i + 1, msg);
+            let mut fields = line.split_whitespace();
+            let key = fields.next().unwrap();
+            let (own, other) = key.split_once('/').ok_or_else(|| err("missing /"))?;
+            let own = Numbers::from_digits(own).ok_or_else(|| err("bad position"))?;
+            let other = Numbers::from_digits(other).ok_or_else(|| err("bad position"))?;
+            let mut candidates = Vec::new();
+            for field in fields {
+                let candidate = field
+                    .split_once(':')
+                    .and_then(|(n, w)| Some((n.parse::<u64>().ok()?, w.parse::<u32>().ok()?)));
+                match candidate {
+                    Some((n, w)) if (1..=9).contains(&n) && w > 0 => candidates.push((n, w)),
+                    _ => return Err(err("bad candidate")),
+                }
+            }
+            if candidates.is_empty() {
+                return Err(err("no candidates"));
+            }
+            if entries.insert(position(&own, &other), candidates).is_some() {
+                return Err(err("duplicate position"));
+            }
+        }
+        Ok(Book { entries })
+    }
+
+    /// Pick a book move for the player holding `own`,
+    /// restricted to the `allowed` moves. Returns `None` if
+    /// the book has nothing to offer.
+    pub fn choose<R: Rng>(
+        &self,
+        own: &Numbers,
+        other: &Numbers,
+        allowed: &[u64],
+        rng: &mut R,
+    ) -> Option<u64> {
+        let candidates: Vec<(u64, u32)> = self
+            .entries
+            .get(&position(own, other))?
+            .iter()
+            .filter(|(n, _)| allowed.contains(n))
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let weights = WeightedIndex::new(candidates.iter().map(|&(_, w)| w)).unwrap();
+        Some(candidates[weights.sample(rng)].0)
+    }
+}
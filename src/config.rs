@@ -0,0 +1,172 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Server configuration, taken from the command line.
+
+use crate::events::Lag;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Command-line usage summary.
+pub const USAGE: &str = "usage: net-15 [options]
+  --nodelay                    disable Nagle's algorithm on client sockets
+  --keepalive <secs>           send TCP keepalives after <secs> idle
+  --keepalive-interval <secs>  seconds between unanswered keepalives
+  --backlog <n>                length of the pending connection queue
+  --linger <secs>              linger on close for up to <secs>
+  --write-timeout <secs>       drop clients that stall output this long
+                               (default 60, 0 to wait forever)
+  --idle-timeout <secs>        drop clients that leave a prompt
+                               unanswered this long (default forever)
+  --access-log <path>          log each connection to <path>
+  --access-log-size <bytes>    rotate the access log past <bytes>
+  --hints <n>                  hints each client gets per game (default 3)
+  --motd <path>                greet clients with the template in <path>
+  --log-queue <n>              game log events held while it catches up
+  --log-lag drop|disconnect    drop old events or stop logging once the
+                               game log queue is full (default drop)
+  --maintenance-message <text> what new clients are told in maintenance
+                               mode, toggled by SIGUSR1
+  --max-bytes <n>              drop clients once they have sent and been
+                               sent <n> bytes in all";
+
+/// Server settings.
+pub struct Config {
+    /// Set `TCP_NODELAY` on client sockets.
+    pub nodelay: bool,
+    /// Idle time before TCP keepalives start, if enabled.
+    pub keepalive: Option<Duration>,
+    /// Time between keepalives once they start.
+    pub keepalive_interval: Option<Duration>,
+    /// Accept backlog for the listening socket.
+    pub backlog: i32,
+    /// `SO_LINGER` timeout for client sockets, if any.
+    pub linger: Option<Duration>,
+    /// How long a write to a client may block before the
+    /// client is dropped, if limited.
+    pub write_timeout: Option<Duration>,
+    /// How long a client may leave a prompt unanswered
+    /// before being dropped, if limited.
+    pub idle_timeout: Option<Duration>,
+    /// Where to log connections, if anywhere.
+    pub access_log: Option<PathBuf>,
+    /// Size at which the access log is rotated.
+    pub access_log_size: u64,
+    /// Hints each client gets per game.
+    pub hints: usize,
+    /// Message of the day template, if any.
+    pub motd: Option<PathBuf>,
+    /// Events the game log may fall behind by.
+    pub log_queue: usize,
+    /// What the game log does when it falls further behind.
+    pub log_lag: Lag,
+    /// What clients connecting in maintenance mode are told.
+    pub maintenance_message: String,
+    /// Most bytes a connection may move in total, if
+    /// limited.
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            nodelay: false,
+            keepalive: None,
+            keepalive_interval: None,
+            backlog: 128,
+            linger: None,
+            write_timeout: Some(Duration::from_secs(60)),
+            idle_timeout: None,
+            access_log: None,
+            access_log_size: 1 << 20,
+            hints: 3,
+            motd: None,
+            log_queue: 1024,
+            log_lag: Lag::DropOldest,
+            maintenance_message: "down for maintenance, back soon".to_string(),
+            max_bytes: None,
+        }
+    }
+}
+
+/// Parse the value following `flag`.
+fn value<T, I>(flag: &str, args: &mut I) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    I: Iterator<Item = String>,
+{
+    let arg = args
+        .next()
+        .ok_or_else(|| format!("{} needs a value", flag))?;
+    arg.parse()
+        .map_err(|_| format!("bad value {:?} for {}", arg, flag))
+}
+
+/// Parse a number of seconds following `flag`.
+fn seconds<I>(flag: &str, args: &mut I) -> Result<Duration, String>
+where
+    I: Iterator<Item = String>,
+{
+    value(flag, args).map(Duration::from_secs)
+}
+
+impl Config {
+    /// Build a configuration from command-line arguments,
+    /// not including the program name.
+    pub fn from_args<I>(args: I) -> Result<Config, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = Config::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--nodelay" => config.nodelay = true,
+                "--keepalive" => config.keepalive = Some(seconds(&arg, &mut args)?),
+                "--keepalive-interval" => {
+                    config.keepalive_interval = Some(seconds(&arg, &mut args)?)
+                }
+                "--backlog" => config.backlog = value(&arg, &mut args)?,
+                "--linger" => config.linger = Some(seconds(&arg, &mut args)?),
+                "--write-timeout" => {
+                    let timeout = seconds(&arg, &mut args)?;
+                    config.write_timeout = Some(timeout).filter(|t| !t.is_zero());
+                }
+                "--idle-timeout" => {
+                    let timeout = seconds(&arg, &mut args)?;
+                    config.idle_timeout = Some(timeout).filter(|t| !t.is_zero());
+                }
+                "--access-log" => config.access_log = Some(value(&arg, &mut args)?),
+                "--access-log-size" => config.access_log_size = value(&arg, &mut args)?,
+                "--hints" => config.hints = value(&arg, &mut args)?,
+                "--motd" => config.motd = Some(value(&arg, &mut args)?),
+                "--log-queue" => config.log_queue = value(&arg, &mut args)?,
+                "--log-lag" => config.log_lag = value(&arg, &mut args)?,
+                "--maintenance-message" => config.maintenance_message = value(&arg, &mut args)?,
+                "--max-bytes" => config.max_bytes = Some(value(&arg, &mut args)?),
+                _ => return Err(format!("unknown option {:?}", arg)),
+            }
+        }
+        #[cfg(not(feature = "tuning"))]
+        if config.keepalive.is_some()
+            || config.linger.is_some()
+            || config.backlog != Config::default().backlog
+        {
+            return Err("--keepalive, --linger and --backlog need the tuning feature".to_string());
+        }
+        #[cfg(not(feature = "maintenance"))]
+        if config.maintenance_message != Config::default().maintenance_message {
+            return Err("--maintenance-message needs the maintenance feature".to_string());
+        }
+        if config.log_queue == 0 {
+            return Err("--log-queue must be at least 1".to_string());
+        }
+        if config.keepalive_interval.is_some() && config.keepalive.is_none() {
+            return Err("--keepalive-interval needs --keepalive".to_string());
+        }
+        Ok(config)
+    }
+}
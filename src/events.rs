@@ -0,0 +1,300 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Server-wide bus for game events.
+//!
+//! Games publish what happens to them; anything that wants
+//! to follow along subscribes and gets its own queue, to
+//! read on its own thread at its own pace. Publishing
+//! never blocks a game.
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Something that happened in a game. Every event carries
+/// the id of its game.
+#[derive(Clone, Debug)]
+pub enum GameEvent {
+    /// A game began for the client at `peer`.
+    Started { game: String, peer: SocketAddr },
+    /// `player` took `number`.
+    Moved {
+        game: String,
+        player: &'static str,
+        number: u64,
+    },
+    /// The position of a game in progress, as a save code,
+    /// whenever someone is about to move.
+    #[cfg(feature = "snapshot")]
+    Position { game: String, code: String },
+    /// A game ended, won by `winner` or drawn, with the
+    /// machine playing at `difficulty` (`mixed` if it changed
+    /// during the game) and the client having used `hints`
+    /// hints.
+    Finished {
+        game: String,
+        players: Vec<String>,
+        winner: Option<String>,
+        difficulty: String,
+        hints: usize,
+    },
+    /// A game ended without a result.
+    Abandoned { game: String, reason: String },
+    /// Bytes read from and written to the client while a
+    /// game was played, sent as the game ends.
+    Traffic {
+        game: String,
+        read: u64,
+        written: u64,
+    },
+}
+
+/// What to do when a subscriber's queue is full.
+#[derive(Clone, Copy, Debug)]
+pub enum Lag {
+    /// Throw away the oldest queued event to make room.
+    DropOldest,
+    /// Stop sending to the subscriber altogether.
+    Disconnect,
+}
+
+impl FromStr for Lag {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(Lag::DropOldest),
+            "disconnect" => Ok(Lag::Disconnect),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Events waiting for one subscriber.
+struct Queue {
+    events: VecDeque<GameEvent>,
+    capacity: usize,
+    lag: Lag,
+    /// Events thrown away since the subscriber last asked.
+    dropped: u64,
+    /// Either end has gone away.
+    closed: bool,
+}
+
+/// A queue shared between the bus and one subscriber.
+type Shared = Arc<(Mutex<Queue>, Condvar)>;
+
+/// Receiving end of a subscription.
+pub struct Subscription {
+    queue: Shared,
+}
+
+impl Subscription {
+    /// Wait for the next event. Returns `None` once the bus
+    /// has given up on this subscriber and nothing is left.
+    pub fn recv(&self) -> Option<GameEvent> {
+        let (queue, ready) = &*self.queue;
+        let mut queue = queue.lock().unwrap();
+        loop {
+            if let Some(event) = queue.events.pop_front() {
+                return Some(event);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = ready.wait(queue).unwrap();
+        }
+    }
+
+    /// Number of events thrown away because this subscriber
+    /// fell behind, since the last time this was asked.
+    pub fn dropped(&self) -> u64 {
+        let mut queue = self.queue.0.lock().unwrap();
+        std::mem::take(&mut queue.dropped)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.queue.0.lock().unwrap().closed = true;
+    }
+}
+
+/// Fan-out of game events to subscribers. Each subscriber
+/// has a bounded queue of its own, so one that falls behind
+/// costs only itself events and never holds up a game.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Shared>>,
+}
+
+impl EventBus {
+    /// Create a bus with no subscribers.
+    pub fn new() -> EventBus {
+        EventBus::default()
+    }
+
+    /// Start receiving every event published from now on,
+    /// with room for `capacity` events not yet received and
+    /// `lag` deciding what happens past that.
+    pub fn subscribe(&self, capacity: usize, lag: Lag) -> Subscription {
+        let queue = Queue {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            lag,
+            dropped: 0,
+            closed: false,
+        };
+        let queue = Arc::new((Mutex::new(queue), Condvar::new()));
+        self.subscribers.lock().unwrap().push(Arc::clone(&queue));
+        Subscription { queue }
+    }
+
+    /// Send an event to every subscriber, forgetting any
+    /// that have hung up or been disconnected for lagging.
+    pub fn publish(&self, event: GameEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|shared| {
+            let (queue, ready) = &**shared;
+            let mut queue = queue.lock().unwrap();
+            if queue.closed {
+                return false;
+            }
+            if queue.events.len() >= queue.capacity {
+                queue.dropped += 1;
+                match queue.lag {
+                    Lag::DropOldest => {
+                        queue.events.pop_front();
+                    }
+                    Lag::Disconnect => {
+                        queue.closed = true;
+                        ready.notify_one();
+                        return false;
+                    }
+                }
+            }
+            queue.events.push_back(event.clone());
+            ready.notify_one();
+            true
+        });
+    }
+}
+
+/// Subscriber that writes the server's game log to
+/// standard output. Events lost to lag are counted in the
+/// log.
+pub fn log_events(events: Subscription) {
+    while let Some(event) = events.recv() {
+        let dropped = events.dropped();
+        if dropped > 0 {
+            println!("events: log fell behind, {} dropped", dropped);
+        }
+        match event {
+            GameEvent::Started { game, peer } => {
+                println!("game {}: started for {:?}", game, peer);
+            }
+            GameEvent::Moved {
+                game,
+                player,
+                number,
+            } => {
+                println!("game {}: {} took {}", game, player, number);
+            }
+            #[cfg(feature = "snapshot")]
+            GameEvent::Position { .. } => (),
+            GameEvent::Finished {
+                game,
+                players,
+                winner,
+                difficulty,
+                hints,
+            } => {
+                let mut result = match winner {
+                    Some(winner) => format!("{} won", winner),
+                    None => "draw".to_string(),
+                };
+                result += &format!(", {} play", difficulty);
+                if hints > 0 {
+                    result += &format!(", {} hints", hints);
+                }
+                println!("game {}: {}: {}", game, players.join(" vs "), result);
+            }
+            GameEvent::Abandoned { game, reason } => {
+                println!("game {}: abandoned: {}", game, reason);
+            }
+            GameEvent::Traffic {
+                game,
+                read,
+                written,
+            } => {
+                println!("game {}: {} bytes in, {} out", game, read, written);
+            }
+        }
+    }
+    println!("events: log fell behind, disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An event for game `n`, to tell events apart.
+    fn event(n: usize) -> GameEvent {
+        GameEvent::Abandoned {
+            game: n.to_string(),
+            reason: "test".to_string(),
+        }
+    }
+
+    /// Id of the game an event is for.
+    fn game(event: GameEvent) -> String {
+        match event {
+            GameEvent::Abandoned { game, .. } => game,
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn lagging_subscriber_keeps_newest_events() {
+        let bus = EventBus::new();
+        let events = bus.subscribe(2, Lag::DropOldest);
+        for n in 0..5 {
+            bus.publish(event(n));
+        }
+        assert_eq!(events.dropped(), 3);
+        assert_eq!(events.dropped(), 0);
+        assert_eq!(game(events.recv().unwrap()), "3");
+        assert_eq!(game(events.recv().unwrap()), "4");
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn lagging_subscriber_is_disconnected() {
+        let bus = EventBus::new();
+        let events = bus.subscribe(2, Lag::Disconnect);
+        for n in 0..3 {
+            bus.publish(event(n));
+        }
+        assert!(bus.subscribers.lock().unwrap().is_empty());
+        assert_eq!(events.dropped(), 1);
+        // What was queued before the overflow is still there.
+        assert_eq!(game(events.recv().unwrap()), "0");
+        assert_eq!(game(events.recv().unwrap()), "1");
+        assert!(events.recv().is_none());
+        bus.publish(event(3));
+        assert!(events.recv().is_none());
+    }
+
+    #[test]
+    fn hung_up_subscriber_is_forgotten() {
+        let bus = EventBus::new();
+        let events = bus.subscribe(2, Lag::DropOldest);
+        drop(events);
+        bus.publish(event(0));
+        assert!(bus.subscribers.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! A two-player game of "15" as a position that moves can
+//! be applied to and undone, for code that wants to explore
+//! the game without the server around it.
+
+use crate::{Numbers, TARGET};
+
+/// How a game stands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// The player to move has numbers to pick from.
+    Playing,
+    /// The player with this index made the target.
+    Won(usize),
+    /// The pool ran out with the target not made.
+    Drawn,
+}
+
+/// The pool, each player's numbers and whose turn it is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub board: Numbers,
+    /// Numbers held by the first and second players.
+    pub hands: [Numbers; 2],
+    /// Index of the player to move.
+    pub mover: usize,
+    /// The sum to make.
+    pub target: u64,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        let mut board = Numbers::new();
+        for n in 1..=9 {
+            board.insert(n);
+        }
+        Position {
+            board,
+            hands: [Numbers::new(), Numbers::new()],
+            mover: 0,
+            target: TARGET,
+        }
+    }
+}
+
+impl Position {
+    /// The start of a standard game: the whole pool, with
+    /// the first player to move, playing to 15.
+    pub fn new() -> Position {
+        Position::default()
+    }
+
+    /// How the game stands.
+    pub fn status(&self) -> Status {
+        if let Some(i) = self.hands.iter().position(|h| h.won(self.target).is_some()) {
+            Status::Won(i)
+        } else if self.board.is_empty() {
+            Status::Drawn
+        } else {
+            Status::Playing
+        }
+    }
+
+    /// The numbers the player to move may take, in
+    /// increasing order. There are none once the game is
+    /// over.
+    pub fn moves(&self) -> Vec<u64> {
+        if self.status() != Status::Playing {
+            return Vec::new();
+        }
+        let mut moves: Vec<u64> = self.board.iter().cloned().collect();
+        moves.sort();
+        moves
+    }
+
+    /// Take `n` for the player to move and pass the turn.
+    /// Returns whether the move was legal; an illegal move
+    /// leaves the position alone.
+    pub fn apply(&mut self, n: u64) -> bool {
+        if self.status() != Status::Playing || !self.board.remove(n) {
+            return false;
+        }
+        self.hands[self.mover].insert(n);
+        self.mover = 1 - self.mover;
+        true
+    }
+
+    /// Take back `n`, the last move made, returning the turn
+    /// to the player who made it. Returns whether that player
+    /// held `n`; if not, the position is left alone.
+    pub fn undo(&mut self, n: u64) -> bool {
+        let last = 1 - self.mover;
+        if !self.hands[last].remove(n) {
+            return false;
+        }
+        self.board.insert(n);
+        self.mover = last;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Does `hand` hold three different numbers making
+    /// `target`?
+    fn has_triple(hand: &Numbers, target: u64) -> bool {
+        let mut ns: Vec<u64> = hand.iter().cloned().collect();
+        ns.sort();
+        ns.dedup();
+        (0..ns.len()).any(|i| {
+            (i + 1..ns.len()).any(|j| (j + 1..ns.len()).any(|k| ns[i] + ns[j] + ns[k] == target))
+        })
+    }
+
+    /// Check every position below `position`, returning how
+    /// many there are, itself included.
+    fn walk(position: &mut Position) -> usize {
+        match position.status() {
+            Status::Won(i) => {
+                assert!(has_triple(&position.hands[i], position.target));
+                assert!(position.moves().is_empty());
+                return 1;
+            }
+            Status::Drawn => {
+                assert!(position
+                    .hands
+                    .iter()
+                    .all(|h| !has_triple(h, position.target)));
+                assert!(position.moves().is_empty());
+                return 1;
+            }
+            Status::Playing => (),
+        }
+        let mut count = 1;
+        for n in position.moves() {
+            let before = position.clone();
+            assert!(position.apply(n));
+            count += walk(position);
+            assert!(position.undo(n));
+            assert_eq!(*position, before);
+        }
+        count
+    }
+
+    #[test]
+    fn game_tree_after_openings() {
+        // Center against corner either way round, and two
+        // edges: a sample of the tree that is quick to walk.
+        for opening in [[5, 2], [2, 5], [1, 9]] {
+            let mut position = Position::new();
+            for n in opening {
+                assert!(position.apply(n));
+            }
+            assert!(walk(&mut position) > 1000);
+        }
+    }
+
+    /// Slow in a debug build; run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn full_game_tree() {
+        assert_eq!(walk(&mut Position::new()), 549946);
+    }
+
+    #[test]
+    fn illegal_moves_leave_the_position_alone() {
+        let mut position = Position::new();
+        assert!(position.apply(5));
+        let before = position.clone();
+        assert!(!position.apply(5));
+        assert!(!position.apply(10));
+        assert!(!position.undo(4));
+        assert_eq!(position, before);
+    }
+}
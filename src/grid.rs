@@ -0,0 +1,103 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Draw the pool as a magic square.
+//!
+//! Every row, column and diagonal of the square sums to 15,
+//! so "15" is tic-tac-toe in disguise. Showing the square is
+//! the clever trick from the README.
+
+use crate::terminal::Terminal;
+use crate::Numbers;
+
+/// The 3×3 magic square.
+const SQUARE: [[u64; 3]; 3] = [[2, 7, 6], [9, 5, 1], [4, 3, 8]];
+
+/// Brackets marking the numbers held by each hand, in
+/// order.
+const MARKS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Characters for drawing a grid: horizontal, vertical,
+/// and the corners and junctions of the top, middle and
+/// bottom rules, each left to right.
+struct Lines {
+    horizontal: &'static str,
+    vertical: &'static str,
+    top: [&'static str; 3],
+    middle: [&'static str; 3],
+    bottom: [&'static str; 3],
+}
+
+const ASCII: Lines = Lines {
+    horizontal: "-",
+    vertical: "|",
+    top: ["+", "+", "+"],
+    middle: ["+", "+", "+"],
+    bottom: ["+", "+", "+"],
+};
+
+const BOX: Lines = Lines {
+    horizontal: "─",
+    vertical: "│",
+    top: ["┌", "┬", "┐"],
+    middle: ["├", "┼", "┤"],
+    bottom: ["└", "┴", "┘"],
+};
+
+/// Draw one horizontal rule.
+fn rule(lines: &Lines, ends: [&str; 3]) -> String {
+    let cell = lines.horizontal.repeat(3);
+    format!(
+        "{}{}{}{}{}{}{}\n",
+        ends[0], cell, ends[1], cell, ends[1], cell, ends[2]
+    )
+}
+
+/// Draw the square showing who holds which number: `(n)`
+/// for the first hand, `[n]` for the second and `{n}` for
+/// the third. The numbers in `win`, if any, are
+/// highlighted, in reverse video where the terminal
+/// supports it and between stars otherwise.
+pub fn render(hands: &[&Numbers], win: Option<&Numbers>, terminal: &Terminal) -> String {
+    let lines = if terminal.utf8 { &BOX } else { &ASCII };
+    let mut out = rule(lines, lines.top);
+    for (i, row) in SQUARE.iter().enumerate() {
+        if i > 0 {
+            out += &rule(lines, lines.middle);
+        }
+        out += lines.vertical;
+        for &n in row {
+            let winning = win.is_some_and(|w| w.contains(n));
+            let cell = if winning && !terminal.ansi {
+                format!("*{}*", n)
+            } else if let Some(i) = hands.iter().position(|h| h.contains(n)) {
+                let (open, close) = MARKS[i];
+                format!("{}{}{}", open, n, close)
+            } else {
+                format!(" {} ", n)
+            };
+            if winning && terminal.ansi {
+                out += &format!("\x1b[7m{}\x1b[0m", cell);
+            } else {
+                out += &cell;
+            }
+            out += lines.vertical;
+        }
+        out += "\n";
+    }
+    out += &rule(lines, lines.bottom);
+    out
+}
+
+/// Say whose numbers each mark shows, given the names of
+/// the hands in order.
+pub fn legend(names: &[&str]) -> String {
+    let keys: Vec<String> = names
+        .iter()
+        .zip(MARKS)
+        .map(|(name, (open, close))| format!("{}n{} {}", open, close, name))
+        .collect();
+    keys.join("  ")
+}
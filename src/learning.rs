@@ -0,0 +1,178 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Experimental machine strategy that learns from its
+//! games.
+//!
+//! The learner keeps a table of values for the positions
+//! the machine has moved into, all starting at an even
+//! 0.5. It usually moves into the best-valued position,
+//! sometimes explores, and after each game backs the result
+//! up along the positions it moved through,
+//! temporal-difference style. There are only 3^9 positions,
+//! so the whole table fits easily in memory. A table loaded
+//! from a file is saved back after every game so learning
+//! survives restarts.
+
+use crate::{Choice, Numbers, Reason, Strategy, TARGET};
+
+use rand::random;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+/// Learning rate.
+const ALPHA: f64 = 0.2;
+
+/// Probability of making an exploratory move.
+const EPSILON: f64 = 0.1;
+
+/// Value of a position never seen before.
+const UNKNOWN: f64 = 0.5;
+
+/// Index of a position, from the point of view of the
+/// player holding `own`.
+pub fn position(own: &Numbers, other: &Numbers) -> u32 {
+    (1..=9).rev().fold(0, |v, n| {
+        let owner = if own.contains(n) {
+            1
+        } else if other.contains(n) {
+            2
+        } else {
+            0
+        };
+        v * 3 + owner
+    })
+}
+
+/// Learned position values, together with the file they
+/// live in, if any. The default learner has learned nothing
+/// and keeps its values only in memory.
+#[derive(Default)]
+pub struct Learner {
+    values: HashMap<u32, f64>,
+    path: Option<PathBuf>,
+}
+
+impl Learner {
+    /// Read a value table from `path`. A missing file just
+    /// means nothing has been learned yet.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Learner, io::Error> {
+        let path = path.into();
+        let mut values = HashMap::new();
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                for line in text.lines() {
+                    let mut fields = line.split_whitespace();
+                    let entry = fields
+                        .next()
+                        .and_then(|s| s.parse::<u32>().ok())
+                        .zip(fields.next().and_then(|v| v.parse::<f64>().ok()));
+                    match entry {
+                        Some((s, v)) => {
+                            values.insert(s, v);
+                        }
+                        None => {
+                            let msg = format!("bad value table line: {:?}", line);
+                            return Err(io::Error::new(ErrorKind::InvalidData, msg));
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        Ok(Learner {
+            values,
+            path: Some(path),
+        })
+    }
+
+    /// Write the value table back to its file, if it has
+    /// one.
+    pub fn save(&self) -> Result<(), io::Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut entries: Vec<(&u32, &f64)> = self.values.iter().collect();
+        entries.sort_by_key(|&(s, _)| *s);
+        let text: String = entries
+            .into_iter()
+            .map(|(s, v)| format!("{} {}\n", s, v))
+            .collect();
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(tmp, path)
+    }
+
+    /// Current value of a position.
+    fn value(&self, state: u32) -> f64 {
+        self.values.get(&state).cloned().unwrap_or(UNKNOWN)
+    }
+
+    /// Pick a number from `board` for the player holding
+    /// `own`. Returns the choice and the position it leads
+    /// to, which the caller should remember for `learn()`.
+    pub fn choose(
+        &self,
+        board: &Numbers,
+        own: &Numbers,
+        other: &Numbers,
+        strategy: Strategy,
+        opening: bool,
+    ) -> (Choice, u32) {
+        let mut moves: Vec<(u64, u32, f64)> = board
+            .iter()
+            .filter(|&&n| !(n == 5 && strategy.no_center_opening && opening && board.len() > 1))
+            .map(|&n| {
+                let mut after = own.clone();
+                after.insert(n);
+                let state = position(&after, other);
+                let value = if after.won(TARGET).is_some() {
+                    1.0
+                } else {
+                    self.value(state)
+                };
+                (n, state, value)
+            })
+            .collect();
+        moves.sort_by_key(|&(n, _, _)| n);
+
+        if random::<f64>() < EPSILON {
+            let (number, state, _) = moves[random::<usize>() % moves.len()];
+            let choice = Choice {
+                number,
+                reason: Reason::Exploring,
+            };
+            return (choice, state);
+        }
+        let best = moves
+            .iter()
+            .map(|&(_, _, v)| v)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let ties: Vec<&(u64, u32, f64)> = moves.iter().filter(|&&(_, _, v)| v == best).collect();
+        let &(number, state, _) = ties[random::<usize>() % ties.len()];
+        let choice = Choice {
+            number,
+            reason: Reason::Learned,
+        };
+        (choice, state)
+    }
+
+    /// Back up the final `reward` of a game (1 for a win, 0
+    /// for a loss, 0.5 for a draw) along the positions the
+    /// learner moved into, last first.
+    pub fn learn(&mut self, trajectory: &[u32], reward: f64) {
+        let mut target = reward;
+        for &state in trajectory.iter().rev() {
+            let value = self.value(state);
+            let value = value + ALPHA * (target - value);
+            self.values.insert(state, value);
+            target = value;
+        }
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! The game engine behind `net15`: numbers and wins, the
+//! machine's strategies and game records. The server itself
+//! lives in `main.rs`; the engine is a library of its own
+//! so that benchmarks and tests can get at it. See `game`
+//! for playing through positions move by move.
+
+pub mod book;
+pub mod game;
+pub mod learning;
+pub mod record;
+pub mod solve;
+use solve::Value;
+
+use rand::random;
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::ops::RangeInclusive;
+
+/// The sum to make in the standard game.
+pub const TARGET: u64 = 15;
+
+/// Sums a game may be played to instead. Each can be made
+/// from the pool in several ways.
+pub const TARGETS: RangeInclusive<u64> = 12..=18;
+
+/// Number of ways to make `target` from three different
+/// numbers of the pool, one of them `n`.
+fn lines(n: u64, target: u64) -> usize {
+    (1..=9u64)
+        .flat_map(|a| (a + 1..=9).map(move |b| (a, b)))
+        .filter(|&(a, b)| a != n && b != n && a + b + n == target)
+        .count()
+}
+
+/// Why the machine picked the number it did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// 5 sits in the center of the magic square, on four
+    /// winning lines.
+    Center,
+    /// Even numbers sit in the corners, on three winning
+    /// lines each.
+    Corner,
+    /// Nothing better was available.
+    Leftover,
+    /// Playing to a sum other than 15, this number can make
+    /// it in the most ways.
+    Lines,
+    /// The learned values rate this move best.
+    Learned,
+    /// The learning strategy is trying something new.
+    Exploring,
+    /// The opening book suggests this move.
+    Book,
+    /// Perfect play, with the result it leads to.
+    Solved(Value),
+    /// This number makes the given sum.
+    Completes(u64),
+    /// An opponent could make the given sum with this
+    /// number.
+    Blocks(u64),
+}
+
+/// A machine move together with its rationale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Choice {
+    pub number: u64,
+    pub reason: Reason,
+}
+
+impl Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "taking {}: ", self.number)?;
+        let why = match self.reason {
+            Reason::Center => "center equivalent",
+            Reason::Corner => "corner equivalent",
+            Reason::Leftover => "no center or corner left",
+            Reason::Lines => "on the most winning lines",
+            Reason::Learned => "best move I know of",
+            Reason::Exploring => "trying something new",
+            Reason::Book => "straight from the book",
+            Reason::Solved(Value::Win) => "forces a win",
+            Reason::Solved(Value::Draw) => "holds the draw",
+            Reason::Solved(Value::Loss) => "nothing saves me now",
+            Reason::Completes(target) => return write!(f, "makes {}", target),
+            Reason::Blocks(target) => return write!(f, "blocks a {}", target),
+        };
+        write!(f, "{}", why)
+    }
+}
+
+/// Modifiers to the machine's strategy and to the rules,
+/// used to give the human a handicap.
+#[derive(Clone, Copy, Default)]
+pub struct Strategy {
+    /// Never take 5 as the machine's first number.
+    pub no_center_opening: bool,
+    /// Number the human was handed before their first move.
+    pub given: Option<u64>,
+}
+
+impl Strategy {
+    /// Hand `n` from `board` to the human's `hand` as a
+    /// handicap. Only one number may be given, and only
+    /// before the human has moved.
+    pub fn give(
+        &mut self,
+        n: u64,
+        board: &mut Numbers,
+        hand: &mut Numbers,
+    ) -> Result<(), &'static str> {
+        if self.given.is_some() || !hand.is_empty() {
+            return Err("too late for that handicap");
+        }
+        if !board.remove(n) {
+            return Err("unavailable choice try again");
+        }
+        hand.insert(n);
+        self.given = Some(n);
+        Ok(())
+    }
+}
+
+/// Thin wrapper around a multiset of numbers, primarily
+/// for `Display`. Each number maps to how many times it is
+/// held; the standard game never holds one twice.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Numbers(HashMap<u64, usize>);
+
+impl Display for Numbers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut elems: Vec<&u64> = self.iter().collect();
+        elems.sort();
+        let result: Vec<String> = elems
+            .into_iter()
+            .flat_map(|n| vec![n.to_string(); self.count(*n)])
+            .collect();
+        let result = result.join(" ");
+        write!(f, "{}", result)
+    }
+}
+
+impl Numbers {
+    /// Create a new empty set of numbers.
+    pub fn new() -> Numbers {
+        Numbers(HashMap::new())
+    }
+
+    /// Parse numbers written as digits with no separators,
+    /// as in `"159"`. Each digit must be `1..=9` and appear
+    /// at most once.
+    pub fn from_digits(digits: &str) -> Option<Numbers> {
+        let mut numbers = Numbers::new();
+        for c in digits.chars() {
+            let n = c.to_digit(10)? as u64;
+            if n == 0 || numbers.contains(n) {
+                return None;
+            }
+            numbers.insert(n);
+        }
+        Some(numbers)
+    }
+
+    /// Write the numbers as digits with no separators, in
+    /// increasing order.
+    pub fn digits(&self) -> String {
+        self.to_string().replace(' ', "")
+    }
+
+    /// Insert a number into the current numbers.
+    pub fn insert(&mut self, e: u64) {
+        *self.0.entry(e).or_insert(0) += 1;
+    }
+
+    /// Remove one copy of a number from the current numbers.
+    pub fn remove(&mut self, e: u64) -> bool {
+        match self.0.get_mut(&e) {
+            Some(1) => {
+                self.0.remove(&e);
+                true
+            }
+            Some(count) => {
+                *count -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Do the current numbers contain three that make
+    /// `target`? Copies of a number count separately, so two
+    /// 3s and a 9 make 15.
+    pub fn won(&self, target: u64) -> Option<Numbers> {
+        let mut elems: Vec<u64> = self.iter().cloned().collect();
+        elems.sort();
+        for (i, &a) in elems.iter().enumerate() {
+            for &b in &elems[i..] {
+                let c = match target.checked_sub(a + b) {
+                    Some(c) if c >= b => c,
+                    _ => continue,
+                };
+                let mut triple = Numbers::new();
+                for n in [a, b, c] {
+                    triple.insert(n);
+                }
+                if triple.iter().all(|&n| self.count(n) >= triple.count(n)) {
+                    return Some(triple);
+                }
+            }
+        }
+        None
+    }
+
+    /// A number from the current numbers that would complete
+    /// a win to `target` for the player holding `hand`, if
+    /// any.
+    pub fn winning_number(&self, hand: &Numbers, target: u64) -> Option<u64> {
+        self.iter().cloned().find(|&n| {
+            let mut after = hand.clone();
+            after.insert(n);
+            after.won(target).is_some()
+        })
+    }
+
+    /// A number that makes `target` for the player holding
+    /// `own`, or failing that one that any of `others` could
+    /// make it with, if there is one.
+    pub fn tactical_choice(
+        &self,
+        own: &Numbers,
+        others: &[&Numbers],
+        target: u64,
+    ) -> Option<Choice> {
+        if let Some(number) = self.winning_number(own, target) {
+            return Some(Choice {
+                number,
+                reason: Reason::Completes(target),
+            });
+        }
+        let number = others
+            .iter()
+            .find_map(|other| self.winning_number(other, target))?;
+        Some(Choice {
+            number,
+            reason: Reason::Blocks(target),
+        })
+    }
+
+    /// Use a randomized heuristic to select a next number,
+    /// playing to `target`: take a number that can make it
+    /// in the most ways. For 15 that is the center of the
+    /// magic square, then the corners. `opening` says whether
+    /// this is the machine's first number, which some
+    /// strategy modifiers care about.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use net_15::{Numbers, Strategy, TARGET};
+    ///
+    /// let mut ns = Numbers::new();
+    /// ns.insert(3);
+    /// ns.insert(4);
+    /// ns.insert(7);
+    /// assert_eq!(ns.heuristic_choice(Strategy::default(), true, TARGET).number, 4);
+    /// ```
+    pub fn heuristic_choice(&self, strategy: Strategy, opening: bool, target: u64) -> Choice {
+        let skip_center = strategy.no_center_opening && opening && self.iter().any(|&n| n != 5);
+        let choices: Vec<u64> = self
+            .iter()
+            .cloned()
+            .filter(|&n| !(skip_center && n == 5))
+            .collect();
+        let most = choices.iter().map(|&n| lines(n, target)).max().unwrap();
+        let choices: Vec<u64> = choices
+            .into_iter()
+            .filter(|&n| lines(n, target) == most)
+            .collect();
+        let reason = match most {
+            _ if target != TARGET => Reason::Lines,
+            4 => Reason::Center,
+            3 => Reason::Corner,
+            _ => Reason::Leftover,
+        };
+        let index = random::<usize>() % choices.len();
+        Choice {
+            number: choices[index],
+            reason,
+        }
+    }
+
+    /// The distinct numbers among the current numbers, in
+    /// no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &u64> {
+        self.0.keys()
+    }
+
+    /// How many copies of the given number are there?
+    pub fn count(&self, e: u64) -> usize {
+        self.0.get(&e).cloned().unwrap_or(0)
+    }
+
+    /// Is the given number among the current numbers?
+    pub fn contains(&self, e: u64) -> bool {
+        self.0.contains_key(&e)
+    }
+
+    /// How many numbers are there, counting copies?
+    pub fn len(&self) -> usize {
+        self.0.values().sum()
+    }
+
+    /// Are there any numbers?
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Is this game being played with duplicates, judging by
+/// every number in the pool and the hands?
+pub fn has_duplicates(board: &Numbers, hands: &[&Numbers]) -> bool {
+    (1..=9).any(|n| board.count(n) + hands.iter().map(|h| h.count(n)).sum::<usize>() > 1)
+}
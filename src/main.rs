@@ -8,108 +8,104 @@
 //! game.
 
 extern crate rand;
-use rand::random;
+use net_15::book::Book;
+use net_15::learning::Learner;
+use net_15::record::{Outcome, Record};
+use net_15::solve::{self, Grade, Solver};
+use net_15::{has_duplicates, Choice, Numbers, Reason, Strategy, TARGET, TARGETS};
+use rand::rngs::StdRng;
+use rand::{random, Rng, SeedableRng};
+#[cfg(feature = "maintenance")]
+use signal_hook::consts::SIGUSR1;
+#[cfg(feature = "snapshot")]
+use signal_hook::consts::{SIGINT, SIGTERM};
+#[cfg(any(feature = "snapshot", feature = "maintenance"))]
+use signal_hook::iterator::Signals;
+#[cfg(feature = "tuning")]
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+
+mod access;
+mod achievements;
+mod config;
+mod events;
+mod grid;
+mod motd;
+mod nim;
+mod pacing;
+mod persona;
+mod prompt;
+mod protocol;
+mod save;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+mod stats;
+mod terminal;
+mod traffic;
+use access::{AccessLog, Visit};
+use achievements::{Achievements, Ending};
+use config::Config;
+use events::{log_events, EventBus, GameEvent, Lag};
+use motd::Motd;
+use pacing::Pacing;
+use persona::Personas;
+use prompt::{Answer, Prompt};
+use protocol::{AnyInput, MoveInput, RematchInput};
+use save::{Mover, SavedGame};
+#[cfg(feature = "snapshot")]
+use snapshot::InProgress;
+use stats::Stats;
+use terminal::Terminal;
 
-use std::collections::HashSet;
 use std::fmt::{self, Display};
 use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
 use std::net::*;
+#[cfg(feature = "snapshot")]
+use std::path::Path;
+#[cfg(feature = "maintenance")]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-/// Thin wrapper around a set of numbers, primarily for
-/// `Display`.
-#[derive(Clone)]
-struct Numbers(HashSet<u64>);
+/// Names of the machine players that can join a game
+/// besides the main one.
+const BOTS: [&str; 1] = ["bot"];
 
-impl Display for Numbers {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut elems: Vec<&u64> = self.0.iter().collect();
-        elems.sort();
-        let result: Vec<String> = elems.into_iter().map(ToString::to_string).collect();
-        let result = result.join(" ");
-        write!(f, "{}", result)
-    }
-}
+/// Pause between moves of a demo game, so that it can be
+/// followed as it is played, unless the client sets its own
+/// pace.
+const DEMO_DELAY: Duration = Duration::from_secs(1);
 
-impl Numbers {
-    /// Create a new empty set of numbers.
-    fn new() -> Numbers {
-        Numbers(HashSet::new())
-    }
-
-    /// Insert a number into the current numbers.
-    fn insert(&mut self, e: u64) {
-        assert!(self.0.insert(e));
-    }
-
-    /// Remove a number from the current numbers.
-    fn remove(&mut self, e: u64) -> bool {
-        self.0.remove(&e)
-    }
-
-    /// Do the current numbers contain a win?
-    fn won(&self) -> Option<Numbers> {
-        self.choose(3)
-            .into_iter()
-            .find(|Numbers(s)| s.iter().sum::<u64>() == 15)
-    }
-
-    /// Use a randomized heuristic to select a next number.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut ns = Numbers::new();
-    /// ns.insert(3);
-    /// ns.insert(4);
-    /// ns.insert(7);
-    /// assert_eq!(ns.heuristic_choice(), 4);
-    /// ```
-    fn heuristic_choice(&self) -> u64 {
-        if self.0.contains(&5) {
-            return 5;
-        }
-        let corners: HashSet<u64> = [2, 4, 6, 8].iter().cloned().collect();
-        let mut choices = &self.0 & &corners;
-        if choices.is_empty() {
-            choices = self.0.clone();
-        }
-        let choicevec: Vec<&u64> = choices.iter().collect();
-        let index = random::<usize>() % choicevec.len();
-        *choicevec[index]
-    }
-
-    /// List every way in which `n` numbers can be chosen
-    /// from the current numbers.
-    fn choose(&self, n: u64) -> Vec<Numbers> {
-        let s = &self.0;
-        if n == 0 || s.len() < n as usize {
-            return Vec::new();
-        }
-        if s.len() == n as usize {
-            return vec![Numbers(s.clone())];
-        }
-        let mut result: Vec<Numbers> = Vec::new();
-        for e in s {
-            let mut t = (*self).clone();
-            t.remove(*e);
-            result.extend(t.choose(n));
-            let v: Vec<Numbers> = t
-                .choose(n - 1)
-                .into_iter()
-                .map(|mut w| {
-                    w.insert(*e);
-                    w
-                })
-                .collect();
-            result.extend(v);
-        }
-        result
-    }
-
-    /// Are there any numbers?
-    fn is_empty(&self) -> bool {
-        self.0.is_empty()
-    }
+/// File the learning strategy keeps its values in.
+#[cfg(feature = "learning")]
+const LEARNING_FILE: &str = "net15-values.txt";
+
+/// Game events the server statistics may fall behind by.
+const STATS_QUEUE: usize = 1024;
+
+/// File games in progress are kept in across a restart.
+#[cfg(feature = "snapshot")]
+const SNAPSHOT_FILE: &str = "net15-sessions.txt";
+
+/// Game events the tracker of games in progress may fall
+/// behind by.
+#[cfg(feature = "snapshot")]
+const SNAPSHOT_QUEUE: usize = 1024;
+
+/// Next game id to hand out. Starts at a random point so
+/// that ids are unlikely to repeat across server restarts.
+static NEXT_GAME_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Whether the server is in maintenance mode, turning new
+/// clients away.
+#[cfg(feature = "maintenance")]
+static MAINTENANCE: AtomicBool = AtomicBool::new(false);
+
+/// Make a short id, unique within this run of the server,
+/// for a new game.
+fn new_game_id() -> String {
+    let id = NEXT_GAME_ID.fetch_add(1, Ordering::Relaxed);
+    format!("{:06x}", id & 0xff_ffff)
 }
 
 // XXX This is arguably an unnecessary generalization given
@@ -121,6 +117,8 @@ impl Numbers {
 struct PlayerState {
     numbers: Numbers,
     name: &'static str,
+    /// How long each move this game took.
+    move_times: Vec<Duration>,
 }
 
 impl PlayerState {
@@ -129,6 +127,114 @@ impl PlayerState {
         PlayerState {
             numbers: Numbers::new(),
             name,
+            move_times: Vec::new(),
+        }
+    }
+
+    /// Write a line summarizing how long this player took
+    /// over their moves.
+    fn write_times(&self, writer: &mut dyn Write) -> Result<(), Error> {
+        let moves = self.move_times.len();
+        if moves == 0 {
+            return Ok(());
+        }
+        let total: Duration = self.move_times.iter().sum();
+        let longest = self.move_times.iter().max().unwrap();
+        writeln!(
+            writer,
+            "{}: {} moves, average {:.1}s, longest {:.1}s",
+            self.name,
+            moves,
+            total.as_secs_f64() / moves as f64,
+            longest.as_secs_f64(),
+        )
+    }
+}
+
+/// What came of a player's turn.
+enum Turn {
+    /// The player took the given number.
+    Moved(u64),
+    /// The player asked to see the record of the game so
+    /// far and still has to move.
+    Export,
+    /// The player asked to watch a demo game and still has
+    /// to move.
+    Demo,
+    /// The player asked to abandon this game and continue
+    /// a saved one instead.
+    Load(SavedGame),
+    /// The player used up a hint and still has to move.
+    Hint,
+    /// The player asked for a game of Nim with the given
+    /// arguments and still has to move.
+    Nim(String),
+    /// The player asked to replay or analyze a record and
+    /// still has to move.
+    Replay { record: String, analyze: bool },
+}
+
+/// How the machine picks its moves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Difficulty {
+    /// The randomized heuristic.
+    #[default]
+    Normal,
+    /// Experimental: values learned from past games.
+    Learning,
+    /// Perfect play, varied by the opening book.
+    Perfect,
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Difficulty::Normal => "normal",
+            Difficulty::Learning => "learning",
+            Difficulty::Perfect => "perfect",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Settings the human can change from the move prompt.
+#[derive(Default)]
+struct Options {
+    /// Have the machine explain its moves.
+    verbose: bool,
+    /// Handicap imposed on the machine's strategy.
+    strategy: Strategy,
+    /// Strategy the machine plays.
+    difficulty: Difficulty,
+    /// What the client's terminal can display.
+    terminal: Terminal,
+    /// How demo games and replays are paced.
+    pacing: Pacing,
+    /// Show the pool as a magic square.
+    grid: bool,
+    /// Extra machine players from the next game on.
+    bots: usize,
+    /// Play with two of each number from the next game on.
+    duplicates: bool,
+    /// Hints left this game.
+    hints: usize,
+    /// Play to a sum picked at random from the next game on.
+    mystery: bool,
+    /// The sum to make this game, set when it starts.
+    target: u64,
+}
+
+impl Options {
+    /// The difficulty the machines actually play at in a
+    /// game with this many players. Only the heuristic knows
+    /// how to play more than one opponent, or with
+    /// duplicates, and the learned values only cover 15.
+    fn playing(&self, players: usize, duplicates: bool) -> Difficulty {
+        let learning = self.difficulty == Difficulty::Learning;
+        if players > 2 || duplicates || (learning && self.target != TARGET) {
+            Difficulty::Normal
+        } else {
+            self.difficulty
         }
     }
 }
@@ -137,17 +243,23 @@ impl PlayerState {
 /// human or machine player.
 trait Player {
     /// Make a move in the current game state, altering the
-    /// state.
+    /// state. `opponents` are the other players, in the
+    /// order they move after this one.
     fn make_move(
         &mut self,
         board: &mut Numbers,
-        opponent: &PlayerState,
+        opponents: &[&PlayerState],
+        options: &mut Options,
         reader: &mut dyn BufRead,
         writer: &mut dyn Write,
-    ) -> Result<(), Error>;
+    ) -> Result<Turn, Error>;
 
     /// Expose the player state readonly for inspection.
     fn state(&self) -> &PlayerState;
+
+    /// Expose the player state for bookkeeping by the game
+    /// loop.
+    fn state_mut(&mut self) -> &mut PlayerState;
 }
 
 /// This player interacts with the human at the console to
@@ -159,125 +271,1076 @@ impl Player for HumanPlayer {
     fn make_move(
         &mut self,
         board: &mut Numbers,
-        opponent: &PlayerState,
+        opponents: &[&PlayerState],
+        options: &mut Options,
         reader: &mut dyn BufRead,
         writer: &mut dyn Write,
-    ) -> Result<(), Error> {
+    ) -> Result<Turn, Error> {
+        let mut hands = vec![&self.0.numbers];
+        hands.extend(opponents.iter().map(|o| &o.numbers));
+        let duplicates = has_duplicates(board, &hands);
+        let standard = opponents.len() == 1 && !duplicates;
         loop {
-            writeln!(writer, "{}: {}", opponent.name, opponent.numbers)?;
+            for opponent in opponents {
+                writeln!(writer, "{}: {}", opponent.name, opponent.numbers)?;
+            }
             writeln!(writer, "{}: {}", self.0.name, self.0.numbers)?;
             writeln!(writer, "available: {}", *board)?;
-            write!(writer, "move: ")?;
-            writer.flush()?;
-            let mut answer = String::new();
-            if let Err(e) = reader.read_line(&mut answer) {
-                if e.kind() == ErrorKind::InvalidData {
+            if options.grid && !duplicates && options.target == TARGET {
+                let mut hands = vec![&self.0.numbers];
+                hands.extend(opponents.iter().map(|o| &o.numbers));
+                let grid = grid::render(&hands, None, &options.terminal);
+                write!(writer, "{}", grid)?;
+                let mut names = vec![self.0.name];
+                names.extend(opponents.iter().map(|o| o.name));
+                writeln!(writer, "{}", grid::legend(&names))?;
+            }
+            match protocol::read::<MoveInput>(reader, writer)? {
+                MoveInput::Take(n) => {
+                    if board.remove(n) {
+                        self.0.numbers.insert(n);
+                        return Ok(Turn::Moved(n));
+                    }
+                    writeln!(writer, "unavailable choice try again")?;
+                }
+                MoveInput::Any(AnyInput::Hangup) => {
+                    return Err(Error::new(ErrorKind::UnexpectedEof, "client hung up"));
+                }
+                MoveInput::Any(AnyInput::Garbled) => {
                     writeln!(writer)?;
                     writeln!(writer, "garbled input")?;
                     eprintln!("garbled input");
-                    continue;
                 }
-                return Err(e);
-            }
-            let n = answer.trim().parse::<u64>();
-            let n = match n {
-                Ok(n) => n,
-                Err(_) => {
-                    writeln!(writer, "bad choice try again")?;
-                    continue;
+                MoveInput::Any(AnyInput::Invalid(msg)) => writeln!(writer, "{}", msg)?,
+                MoveInput::Save | MoveInput::Load(_) if !standard => {
+                    writeln!(writer, "save codes only cover standard games")?;
+                }
+                #[cfg(feature = "snapshot")]
+                MoveInput::Resume(_) if !standard => {
+                    writeln!(writer, "save codes only cover standard games")?;
+                }
+                MoveInput::Save => {
+                    let saved = SavedGame {
+                        board: board.clone(),
+                        human: self.0.numbers.clone(),
+                        machine: opponents[0].numbers.clone(),
+                        mover: Mover::Human,
+                        target: options.target,
+                        given: options.strategy.given,
+                    };
+                    writeln!(writer, "save code: {}", saved.encode())?;
+                }
+                MoveInput::Load(code) => match SavedGame::decode(&code) {
+                    Ok(saved) => return Ok(Turn::Load(saved)),
+                    Err(e) => writeln!(writer, "bad save code: {}", e)?,
+                },
+                #[cfg(feature = "snapshot")]
+                MoveInput::Resume(game) => {
+                    match snapshot::resume(&game).map(|code| SavedGame::decode(&code)) {
+                        Some(Ok(saved)) => return Ok(Turn::Load(saved)),
+                        _ => writeln!(writer, "no game {} to resume", game)?,
+                    }
+                }
+                MoveInput::Any(AnyInput::Export) => return Ok(Turn::Export),
+                MoveInput::Hint if !standard => {
+                    writeln!(writer, "hints only cover standard games")?;
+                }
+                MoveInput::Hint if options.hints == 0 => {
+                    writeln!(writer, "no hints left this game")?;
+                }
+                MoveInput::Hint => return Ok(Turn::Hint),
+                MoveInput::Any(AnyInput::Demo) => return Ok(Turn::Demo),
+                MoveInput::Any(AnyInput::Nim(args)) => return Ok(Turn::Nim(args)),
+                MoveInput::Any(AnyInput::Replay { record, analyze }) => {
+                    return Ok(Turn::Replay { record, analyze });
+                }
+                MoveInput::Term(args) => {
+                    if args.trim().is_empty() {
+                        writeln!(writer, "term: {}", options.terminal)?;
+                    } else if let Err(e) = options.terminal.configure(&args) {
+                        writeln!(writer, "term: {}", e)?;
+                    } else {
+                        writeln!(writer, "term: {}", options.terminal)?;
+                    }
+                }
+                MoveInput::Pace(args) => {
+                    if args.trim().is_empty() {
+                        writeln!(writer, "pace: {}", options.pacing)?;
+                    } else if let Err(e) = options.pacing.configure(&args) {
+                        writeln!(writer, "pace: {}", e)?;
+                    } else {
+                        writeln!(writer, "pace: {}", options.pacing)?;
+                    }
+                }
+                MoveInput::Grid => {
+                    options.grid = !options.grid;
+                    let state = if options.grid { "on" } else { "off" };
+                    writeln!(writer, "grid {}", state)?;
+                }
+                MoveInput::Verbose => {
+                    options.verbose = !options.verbose;
+                    let state = if options.verbose { "on" } else { "off" };
+                    writeln!(writer, "verbose {}", state)?;
+                }
+                MoveInput::HandicapCenter => {
+                    if opponents.iter().all(|o| o.numbers.is_empty()) {
+                        options.strategy.no_center_opening = true;
+                        let names: Vec<&str> = opponents.iter().map(|o| o.name).collect();
+                        writeln!(writer, "{} won't open with 5", names.join(" and "))?;
+                    } else {
+                        writeln!(writer, "too late for that handicap")?;
+                    }
+                }
+                MoveInput::HandicapGive(n) => {
+                    match options.strategy.give(n, board, &mut self.0.numbers) {
+                        Ok(()) => writeln!(writer, "{} start with {}", self.0.name, n)?,
+                        Err(e) => writeln!(writer, "{}", e)?,
+                    }
+                }
+                MoveInput::Mystery => {
+                    options.mystery = !options.mystery;
+                    let state = if options.mystery { "on" } else { "off" };
+                    writeln!(writer, "mystery target {} from next game", state)?;
+                }
+                MoveInput::Duplicates => {
+                    options.duplicates = !options.duplicates;
+                    let state = if options.duplicates { "on" } else { "off" };
+                    writeln!(writer, "duplicates {} from next game", state)?;
+                }
+                MoveInput::Players(n) => {
+                    options.bots = n - 2;
+                    writeln!(writer, "{} players from next game", n)?;
+                }
+                MoveInput::Difficulty(difficulty) => {
+                    options.difficulty = difficulty;
+                    writeln!(writer, "difficulty {}", difficulty)?;
                 }
-            };
-            if board.remove(n) {
-                self.0.numbers.insert(n);
-                break;
             }
-            writeln!(writer, "unavailable choice try again")?;
         }
-        Ok(())
     }
 
     /// Expose our state.
     fn state(&self) -> &PlayerState {
         &self.0
     }
+
+    /// Expose our state mutably.
+    fn state_mut(&mut self) -> &mut PlayerState {
+        &mut self.0
+    }
 }
 
-struct MachinePlayer(PlayerState);
+/// This player picks its moves by machine, using the
+/// strategy for the current difficulty.
+struct MachinePlayer {
+    state: PlayerState,
+    /// Values shared by every game using the learning
+    /// strategy.
+    learner: Arc<Mutex<Learner>>,
+    /// Positions moved into by the learning strategy so far
+    /// this game.
+    trajectory: Vec<u32>,
+    /// Opening book for the perfect strategy.
+    book: Book,
+    /// Source of the perfect strategy's variety, and of what
+    /// the persona says.
+    rng: StdRng,
+    /// Who the machine plays as at each difficulty.
+    personas: Personas,
+}
+
+impl MachinePlayer {
+    /// Create a machine player sharing the given learner.
+    fn new(name: &'static str, learner: Arc<Mutex<Learner>>, book: Book, rng: StdRng) -> Self {
+        MachinePlayer {
+            state: PlayerState::new(name),
+            learner,
+            trajectory: Vec::new(),
+            book,
+            rng,
+            personas: Personas::standard(),
+        }
+    }
+
+    /// Pick a perfect move to `target`, preferring the
+    /// book's suggestions among equally good ones. The book
+    /// only covers 15.
+    fn perfect_choice(
+        &mut self,
+        board: &Numbers,
+        opponent: &Numbers,
+        strategy: Strategy,
+        target: u64,
+    ) -> Choice {
+        let own = &self.state.numbers;
+        let (value, mut moves) = Solver::new(target).best_moves(board, own, opponent);
+        if strategy.no_center_opening && own.is_empty() && board.len() > 1 {
+            moves.retain(|&n| n != 5);
+            if moves.is_empty() {
+                // Only 5 was best; the handicap costs us.
+                let mut avoid = board.clone();
+                avoid.remove(5);
+                moves = avoid.iter().cloned().collect();
+            }
+        }
+        if target == TARGET {
+            if let Some(number) = self.book.choose(own, opponent, &moves, &mut self.rng) {
+                return Choice {
+                    number,
+                    reason: Reason::Book,
+                };
+            }
+        }
+        let number = moves[self.rng.gen_range(0..moves.len())];
+        Choice {
+            number,
+            reason: Reason::Solved(value),
+        }
+    }
+
+    /// Forget the previous game, ready to play a new one.
+    fn new_game(&mut self) {
+        self.state = PlayerState::new(self.state.name);
+        self.trajectory.clear();
+    }
+
+    /// Feed the result of a finished game back to the
+    /// learner, if it took part.
+    fn learn(&mut self, reward: f64) {
+        if self.trajectory.is_empty() {
+            return;
+        }
+        let mut learner = self.learner.lock().unwrap();
+        learner.learn(&self.trajectory, reward);
+        if let Err(e) = learner.save() {
+            eprintln!("couldn't save learned values: {}", e);
+        }
+        self.trajectory.clear();
+    }
+}
 
 impl Player for MachinePlayer {
     /// Select a machine move and make it.
     fn make_move(
         &mut self,
         board: &mut Numbers,
-        _: &PlayerState,
+        opponents: &[&PlayerState],
+        options: &mut Options,
         _: &mut dyn BufRead,
         writer: &mut dyn Write,
-    ) -> Result<(), Error> {
-        let choice = board.heuristic_choice();
-        writeln!(writer, "{} choose {}", self.0.name, choice)?;
-        board.remove(choice);
-        self.0.numbers.insert(choice);
-        Ok(())
+    ) -> Result<Turn, Error> {
+        let opening = self.state.numbers.is_empty();
+        let opponent = opponents[0];
+        let others: Vec<&Numbers> = opponents.iter().map(|o| &o.numbers).collect();
+        let mut hands = others.clone();
+        hands.push(&self.state.numbers);
+        let duplicates = has_duplicates(board, &hands);
+        let difficulty = options.playing(opponents.len() + 1, duplicates);
+        let (strategy, target) = (options.strategy, options.target);
+        let choice = match difficulty {
+            // With duplicates, center and corners matter less
+            // than making and blocking 15s.
+            Difficulty::Normal if duplicates => board
+                .tactical_choice(&self.state.numbers, &others, target)
+                .unwrap_or_else(|| board.heuristic_choice(strategy, opening, target)),
+            Difficulty::Normal => board.heuristic_choice(strategy, opening, target),
+            Difficulty::Learning => {
+                let learner = self.learner.lock().unwrap();
+                let (choice, position) = learner.choose(
+                    board,
+                    &self.state.numbers,
+                    &opponent.numbers,
+                    options.strategy,
+                    opening,
+                );
+                self.trajectory.push(position);
+                choice
+            }
+            Difficulty::Perfect => self.perfect_choice(board, &opponent.numbers, strategy, target),
+        };
+        writeln!(writer, "{} choose {}", self.state.name, choice.number)?;
+        if options.verbose {
+            writeln!(writer, "({})", choice)?;
+        }
+        board.remove(choice.number);
+        self.state.numbers.insert(choice.number);
+        Ok(Turn::Moved(choice.number))
     }
 
     /// Expose our state.
     fn state(&self) -> &PlayerState {
-        &self.0
+        &self.state
+    }
+
+    /// Expose our state mutably.
+    fn state_mut(&mut self) -> &mut PlayerState {
+        &mut self.state
+    }
+}
+
+/// Play through a game record given by the client, grading
+/// each move against perfect play if `analyze` is set.
+fn replay(text: &str, analyze: bool, writer: &mut dyn Write, pacing: Pacing) -> Result<(), Error> {
+    let mut paced = pacing.writer(writer);
+    let writer: &mut dyn Write = &mut paced;
+    let record = match Record::parse(text) {
+        Ok(record) => record,
+        Err(e) => {
+            writeln!(writer, "bad record: {}", e)?;
+            return Ok(());
+        }
+    };
+    let [first, second] = &record.players;
+    let annotations = if analyze {
+        solve::analyze(&record)
+    } else {
+        Vec::new()
+    };
+    for (i, n) in record.moves.iter().enumerate() {
+        let player = if i % 2 == 0 { first } else { second };
+        if i > 0 {
+            writer.flush()?;
+            pacing.pause(Duration::ZERO);
+        }
+        write!(writer, "{}. {} choose {}", i + 1, player, n)?;
+        match annotations.get(i) {
+            Some(a) if a.grade == Grade::Best => writeln!(writer, ": best ({})", a.value)?,
+            Some(a) => {
+                let best: Vec<String> = a.best.iter().map(ToString::to_string).collect();
+                let best = best.join(" ");
+                writeln!(writer, ": {} ({}), best was {}", a.grade, a.value, best)?;
+            }
+            None => writeln!(writer)?,
+        }
+    }
+    match record.outcome {
+        Outcome::FirstWins => writeln!(writer, "{} win", first)?,
+        Outcome::SecondWins => writeln!(writer, "{} win", second)?,
+        Outcome::Draw => writeln!(writer, "draw")?,
+        Outcome::Unfinished => writeln!(writer, "unfinished")?,
+    }
+    Ok(())
+}
+
+/// Play a game between two machine players, with a pause
+/// between moves, for the client to watch. Each side plays
+/// at a randomly chosen difficulty and explains its moves.
+fn demo(
+    writer: &mut dyn Write,
+    learner: &Arc<Mutex<Learner>>,
+    terminal: Terminal,
+    pacing: Pacing,
+) -> Result<(), Error> {
+    let mut paced = pacing.writer(writer);
+    let writer: &mut dyn Write = &mut paced;
+    let mut board = Numbers::new();
+    for i in 1..=9 {
+        board.insert(i);
+    }
+    let mut players = ["red", "blue"].map(|name| {
+        let rng = StdRng::from_entropy();
+        MachinePlayer::new(name, Arc::clone(learner), Book::standard(), rng)
+    });
+    let mut options = [Difficulty::Normal, Difficulty::Perfect].map(|difficulty| Options {
+        verbose: true,
+        difficulty,
+        terminal,
+        target: TARGET,
+        ..Options::default()
+    });
+    if random::<bool>() {
+        options.swap(0, 1);
+    }
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "demo: red ({}) vs blue ({})",
+        options[0].difficulty, options[1].difficulty
+    )?;
+    for turn in 0.. {
+        let [red, blue] = &mut players;
+        let (player, opponent) = if turn % 2 == 0 {
+            (red, blue)
+        } else {
+            (blue, red)
+        };
+        pacing.pause(DEMO_DELAY);
+        writeln!(writer)?;
+        player.make_move(
+            &mut board,
+            &[opponent.state()],
+            &mut options[turn % 2],
+            &mut std::io::empty(),
+            writer,
+        )?;
+        writer.flush()?;
+        if let Some(win) = player.state().numbers.won(TARGET) {
+            writeln!(writer)?;
+            writeln!(writer, "{}", win)?;
+            let result = format!("{} win", player.state().name);
+            writeln!(writer, "{}", terminal.bold(&result))?;
+            break;
+        }
+        if board.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "{}", terminal.bold("draw"))?;
+            break;
+        }
     }
+    writeln!(writer, "end of demo")?;
+    Ok(())
 }
 
-/// Run a single game, communicating with the human player over the given reader and writer.
-fn game_loop<T, U>(mut reader: T, mut writer: U) -> Result<(), Error>
+/// Publish a game event, and show the client what the
+/// machine's persona for the current difficulty has to say
+/// about it.
+fn announce(
+    events: &EventBus,
+    event: GameEvent,
+    machine: &mut MachinePlayer,
+    options: &Options,
+    writer: &mut dyn Write,
+) -> Result<(), Error> {
+    let difficulty = options.difficulty.to_string();
+    let name = machine.state.name;
+    if let Some(line) = machine
+        .personas
+        .react(&difficulty, &event, name, &mut machine.rng)
+    {
+        writeln!(writer, "{}", options.terminal.wrap(&line))?;
+    }
+    events.publish(event);
+    Ok(())
+}
+
+/// What came of a game with the client.
+struct Played {
+    /// The record of the game, if it was a standard game:
+    /// two players, no duplicates.
+    record: Option<Record>,
+    /// How the game ended for the client.
+    ending: Ending,
+    /// The level the machines played at throughout, if it
+    /// didn't change during the game.
+    difficulty: Option<Difficulty>,
+}
+
+/// Run a single game, communicating with the human player
+/// over the given reader and writer. The human and the
+/// machine may be joined by further machine players, as set
+/// in `options`; everyone takes turns in seat order, human
+/// first.
+fn game_loop<T, U>(
+    mut reader: T,
+    mut writer: U,
+    machine: &mut MachinePlayer,
+    options: &mut Options,
+    human_opens: bool,
+    id: &str,
+    events: &EventBus,
+) -> Result<Played, Error>
 where
     T: BufRead,
     U: Write,
 {
+    let duplicates = options.duplicates;
+    let hints = options.hints;
+    options.target = if options.mystery {
+        rand::thread_rng().gen_range(TARGETS)
+    } else {
+        TARGET
+    };
+    let copies = if duplicates { 2 } else { 1 };
     let mut board = Numbers::new();
     for i in 1..=9 {
-        board.insert(i);
+        for _ in 0..copies {
+            board.insert(i);
+        }
     }
     let mut human = HumanPlayer(PlayerState::new("you"));
-    let mut machine = MachinePlayer(PlayerState::new("I"));
-    let mut turn = random::<usize>() % 2;
-    loop {
-        let (player, opponent): (&mut dyn Player, &dyn Player) = if turn % 2 == 0 {
-            (&mut human, &machine)
-        } else {
-            (&mut machine, &human)
-        };
+    machine.new_game();
+    let mut bots: Vec<MachinePlayer> = BOTS[..options.bots]
+        .iter()
+        .map(|&name| {
+            let rng = StdRng::from_entropy();
+            MachinePlayer::new(name, Arc::clone(&machine.learner), Book::standard(), rng)
+        })
+        .collect();
+    let mut names = vec![human.0.name, machine.state.name];
+    names.extend(bots.iter().map(|bot| bot.state.name));
+    let seats = names.len();
+    let standard = seats == 2 && !duplicates;
+    let mut turn: usize = if human_opens { 0 } else { 1 };
+    if seats > 2 {
+        let order: Vec<&str> = (turn..turn + seats).map(|i| names[i % seats]).collect();
+        writeln!(writer, "players in turn: {}", order.join(", "))?;
+    }
+    if duplicates {
+        writeln!(writer, "the pool has two of each number")?;
+    }
+    if options.mystery {
+        writeln!(writer, "mystery target: make {} to win", options.target)?;
+    }
+    let level = options.playing(seats, duplicates);
+    if level != options.difficulty {
+        writeln!(writer, "in this game the machines play normal")?;
+    }
+    let mut record = if human_opens {
+        Record::new("you", "I", [Numbers::new(), Numbers::new()])
+    } else {
+        Record::new("I", "you", [Numbers::new(), Numbers::new()])
+    };
+    record.target = options.target;
+    record.tags.push(("Game".to_string(), id.to_string()));
+    // How long each recorded move took, for the `Times` tag.
+    let mut times = Vec::new();
+    // The level the machines first moved at, and whether
+    // they have since moved at another.
+    let mut played = None;
+    let mut mixed = false;
+    let winner = loop {
+        #[cfg(feature = "snapshot")]
+        if standard {
+            let mover = if turn.is_multiple_of(2) {
+                Mover::Human
+            } else {
+                Mover::Machine
+            };
+            let saved = SavedGame {
+                board: board.clone(),
+                human: human.0.numbers.clone(),
+                machine: machine.state.numbers.clone(),
+                mover,
+                target: options.target,
+                given: options.strategy.given,
+            };
+            events.publish(GameEvent::Position {
+                game: id.to_string(),
+                code: saved.encode(),
+            });
+        }
+        let seat = turn % seats;
+        if seat != 0 {
+            let playing = options.playing(seats, duplicates);
+            mixed |= *played.get_or_insert(playing) != playing;
+        }
+        let mut players: Vec<&mut dyn Player> = vec![&mut human, &mut *machine];
+        players.extend(bots.iter_mut().map(|bot| bot as &mut dyn Player));
+        let (before, rest) = players.split_at_mut(seat);
+        let (player, after) = rest.split_first_mut().unwrap();
+        let opponents: Vec<&PlayerState> = after
+            .iter()
+            .chain(before.iter())
+            .map(|other| other.state())
+            .collect();
         writeln!(writer)?;
-        player.make_move(&mut board, opponent.state(), &mut reader, &mut writer)?;
-        if let Some(win) = player.state().numbers.won() {
+        let start = Instant::now();
+        let turn_result =
+            player.make_move(&mut board, &opponents, options, &mut reader, &mut writer)?;
+        if let Some(n) = options.strategy.given {
+            // A number given as a handicap was the human's
+            // before either side moved.
+            let seat = record.players.iter().position(|p| p == names[0]).unwrap();
+            if !record.start[seat].contains(n) {
+                record.start[seat].insert(n);
+            }
+        }
+        let n = match turn_result {
+            Turn::Moved(n) => n,
+            Turn::Export => {
+                if standard {
+                    writeln!(writer, "{}", options.terminal.wrap(&record.to_string()))?;
+                } else {
+                    writeln!(writer, "records only cover standard games")?;
+                }
+                continue;
+            }
+            Turn::Demo => {
+                demo(
+                    &mut writer,
+                    &machine.learner,
+                    options.terminal,
+                    options.pacing,
+                )?;
+                continue;
+            }
+            Turn::Hint => {
+                options.hints -= 1;
+                let (value, moves) = Solver::new(options.target).best_moves(
+                    &board,
+                    &human.0.numbers,
+                    &machine.state.numbers,
+                );
+                let moves: Vec<String> = moves.iter().map(ToString::to_string).collect();
+                writeln!(
+                    writer,
+                    "hint: take {} ({}), {} left",
+                    moves.join(" or "),
+                    value,
+                    options.hints
+                )?;
+                continue;
+            }
+            Turn::Nim(args) => {
+                nim::play(&args, &mut reader, &mut writer)?;
+                continue;
+            }
+            Turn::Replay {
+                record: text,
+                analyze,
+            } => {
+                replay(&text, analyze, &mut writer, options.pacing)?;
+                continue;
+            }
+            Turn::Load(saved) => {
+                board = saved.board;
+                human.0.numbers = saved.human;
+                machine.state.numbers = saved.machine;
+                machine.trajectory.clear();
+                let (mover, other) = match saved.mover {
+                    Mover::Human => (&human.0, &machine.state),
+                    Mover::Machine => (&machine.state, &human.0),
+                };
+                let start = [mover.numbers.clone(), other.numbers.clone()];
+                record.restart(mover.name, other.name, start);
+                times.clear();
+                record.target = saved.target;
+                options.target = saved.target;
+                options.strategy.given = saved.given;
+                turn = match saved.mover {
+                    Mover::Human => 0,
+                    Mover::Machine => 1,
+                };
+                writeln!(writer, "game loaded")?;
+                if options.target != TARGET {
+                    writeln!(writer, "mystery target: make {} to win", options.target)?;
+                }
+                continue;
+            }
+        };
+        let elapsed = start.elapsed();
+        player.state_mut().move_times.push(elapsed);
+        let won = player.state().numbers.won(options.target);
+        record.moves.push(n);
+        times.push(elapsed);
+        let moved = GameEvent::Moved {
+            game: id.to_string(),
+            player: names[seat],
+            number: n,
+        };
+        announce(events, moved, machine, options, &mut writer)?;
+        if let Some(win) = won {
             writeln!(writer)?;
             writeln!(writer, "{}", win)?;
-            writeln!(writer, "{} win", player.state().name)?;
-            return Ok(());
+            if options.grid && !duplicates && options.target == TARGET {
+                let mut hands = vec![&human.0.numbers, &machine.state.numbers];
+                hands.extend(bots.iter().map(|bot| &bot.state.numbers));
+                let grid = grid::render(&hands, Some(&win), &options.terminal);
+                write!(writer, "{}", grid)?;
+            }
+            let result = format!("{} win", names[seat]);
+            writeln!(writer, "{}", options.terminal.bold(&result))?;
+            break Some(seat);
         }
         if board.is_empty() {
             writeln!(writer)?;
-            writeln!(writer, "draw")?;
-            return Ok(());
+            writeln!(writer, "{}", options.terminal.bold("draw"))?;
+            break None;
         }
         turn += 1;
+    };
+    human.0.write_times(&mut writer)?;
+    machine.state.write_times(&mut writer)?;
+    for bot in &bots {
+        bot.state.write_times(&mut writer)?;
     }
+    let reward = match winner {
+        Some(1) => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    };
+    machine.learn(reward);
+    record.outcome = match winner {
+        Some(_) if record.moves.len() % 2 == 1 => Outcome::FirstWins,
+        Some(_) => Outcome::SecondWins,
+        None => Outcome::Draw,
+    };
+    let hints = hints - options.hints;
+    if hints > 0 {
+        record.tags.push(("Hints".to_string(), hints.to_string()));
+    }
+    if !times.is_empty() {
+        let times: Vec<String> = times
+            .iter()
+            .map(|t| format!("{:.1}", t.as_secs_f64()))
+            .collect();
+        record.tags.push(("Times".to_string(), times.join(" ")));
+    }
+    // Machines that never moved, say in a game won straight
+    // after a load, count as playing the level it started at.
+    let difficulty = Some(played.unwrap_or(level)).filter(|_| !mixed);
+    let finished = GameEvent::Finished {
+        game: id.to_string(),
+        players: names.iter().map(ToString::to_string).collect(),
+        winner: winner.map(|seat| names[seat].to_string()),
+        difficulty: difficulty.map_or("mixed".to_string(), |d| d.to_string()),
+        hints,
+    };
+    announce(events, finished, machine, options, &mut writer)?;
+    let ending = match winner {
+        Some(0) => Ending::Won(human.0.numbers.len()),
+        Some(_) => Ending::Lost,
+        None => Ending::Drawn,
+    };
+    Ok(Played {
+        record: Some(record).filter(|_| standard),
+        ending,
+        difficulty,
+    })
+}
+
+/// Server-wide state and settings every session works
+/// with.
+#[derive(Clone)]
+struct Shared {
+    /// Values shared by every game using the learning
+    /// strategy.
+    learner: Arc<Mutex<Learner>>,
+    /// Where games publish their events.
+    events: Arc<EventBus>,
+    /// Statistics for the message of the day.
+    stats: Arc<Stats>,
+    /// Message of the day, if any.
+    motd: Option<Arc<Motd>>,
+    /// Hints each client gets per game.
+    hints: usize,
+}
+
+/// Play games with one client until they decline a
+/// rematch. A coin toss decides who opens the first game;
+/// after that the opening alternates.
+fn session<T, U>(
+    mut reader: T,
+    mut writer: U,
+    shared: &Shared,
+    visit: &mut Visit,
+) -> Result<(), Error>
+where
+    T: BufRead,
+    U: Write,
+{
+    let (events, stats) = (&*shared.events, &*shared.stats);
+    let rng = StdRng::from_entropy();
+    let learner = Arc::clone(&shared.learner);
+    let mut machine = MachinePlayer::new("I", learner, Book::standard(), rng);
+    let mut options = Options::default();
+    let mut achievements = Achievements::new();
+    let mut human_opens = random::<bool>();
+    let mut id = new_game_id();
+    // https://stackoverflow.com/a/27841363
+    writeln!(writer, "n15 {} game {}", env!("CARGO_PKG_VERSION"), id)?;
+    let _presence = stats.arrive();
+    let answer = Prompt::new(terminal::QUESTION).ask(&mut reader, &mut writer, |line| {
+        options.terminal.answer(line)
+    })?;
+    match answer {
+        Answer::Line(Ok(())) => (),
+        Answer::Line(Err(e)) => writeln!(writer, "term: {}, assuming plain", e)?,
+        Answer::Garbled | Answer::TooLong => writeln!(writer, "term: assuming plain")?,
+        Answer::Hangup => return Err(Error::new(ErrorKind::UnexpectedEof, "client hung up")),
+    }
+    if let Some(motd) = &shared.motd {
+        let message = options.terminal.wrap(&motd.render(stats));
+        write!(writer, "{}", message)?;
+        if !message.ends_with('\n') {
+            writeln!(writer)?;
+        }
+    }
+    writeln!(writer)?;
+    if human_opens {
+        writeln!(writer, "coin toss: you open")?;
+    } else {
+        writeln!(writer, "coin toss: I open")?;
+    }
+    loop {
+        // Handicaps only last for the game they were asked for.
+        options.strategy = Strategy::default();
+        options.hints = shared.hints;
+        let started = GameEvent::Started {
+            game: id.clone(),
+            peer: visit.peer(),
+        };
+        let (read, written) = (visit.traffic().read(), visit.traffic().written());
+        announce(events, started, &mut machine, &options, &mut writer)?;
+        let result = game_loop(
+            &mut reader,
+            &mut writer,
+            &mut machine,
+            &mut options,
+            human_opens,
+            &id,
+            events,
+        );
+        events.publish(GameEvent::Traffic {
+            game: id.clone(),
+            read: visit.traffic().read() - read,
+            written: visit.traffic().written() - written,
+        });
+        let played = match result {
+            Ok(played) => played,
+            Err(e) => {
+                events.publish(GameEvent::Abandoned {
+                    game: id,
+                    reason: e.to_string(),
+                });
+                return Err(e);
+            }
+        };
+        visit.games += 1;
+        let perfect = played.difficulty == Some(Difficulty::Perfect);
+        for line in achievements.judge(played.ending, perfect) {
+            writeln!(writer, "{}", options.terminal.wrap(&line))?;
+        }
+        let record = played.record;
+
+        loop {
+            writeln!(writer)?;
+            match protocol::read::<RematchInput>(&mut reader, &mut writer)? {
+                RematchInput::Rematch => break,
+                RematchInput::Any(AnyInput::Export) => match &record {
+                    Some(record) => {
+                        writeln!(writer, "{}", options.terminal.wrap(&record.to_string()))?
+                    }
+                    None => writeln!(writer, "records only cover standard games")?,
+                },
+                RematchInput::Any(AnyInput::Demo) => demo(
+                    &mut writer,
+                    &machine.learner,
+                    options.terminal,
+                    options.pacing,
+                )?,
+                RematchInput::Achievements => {
+                    for line in achievements.list() {
+                        writeln!(writer, "{}", options.terminal.wrap(&line))?;
+                    }
+                }
+                RematchInput::Any(AnyInput::Nim(args)) => {
+                    nim::play(&args, &mut reader, &mut writer)?
+                }
+                RematchInput::Any(AnyInput::Replay { record, analyze }) => {
+                    replay(&record, analyze, &mut writer, options.pacing)?
+                }
+                RematchInput::Any(AnyInput::Garbled) => writeln!(writer, "garbled input")?,
+                RematchInput::Any(AnyInput::Invalid(msg)) => writeln!(writer, "{}", msg)?,
+                RematchInput::Quit | RematchInput::Any(AnyInput::Hangup) => return Ok(()),
+            }
+        }
+        human_opens = !human_opens;
+        id = new_game_id();
+        writeln!(writer)?;
+        writeln!(writer, "game {}", id)?;
+        if human_opens {
+            writeln!(writer, "I opened last game, you open this one")?;
+        } else {
+            writeln!(writer, "you opened last game, I open this one")?;
+        }
+    }
+}
+
+/// Play with a client over its socket until the session
+/// ends, noting in `visit` why it did.
+fn serve(socket: TcpStream, shared: &Shared, visit: &mut Visit) {
+    let addr = visit.peer();
+    let writer = visit.count(socket.try_clone().unwrap());
+    let reader = BufReader::new(visit.count(socket));
+    match session(reader, writer, shared, visit) {
+        Ok(()) => visit.ended("quit"),
+        Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+            println!("client {:?}: write timed out, dropping", addr);
+            visit.ended("write timeout");
+        }
+        Err(e) => {
+            println!("client {:?}: {}", addr, e);
+            visit.ended(&e.to_string());
+        }
+    }
+}
+
+/// Bring back the games the last server saved, and save the
+/// games in progress when this one is told to shut down.
+#[cfg(feature = "snapshot")]
+fn keep_games(events: &EventBus) {
+    match snapshot::restore(Path::new(SNAPSHOT_FILE)) {
+        Ok(0) => (),
+        Ok(n) => println!("games waiting to be resumed: {}", n),
+        Err(e) => eprintln!("couldn't restore games in progress: {}", e),
+    }
+    let in_progress = Arc::new(InProgress::default());
+    let positions = events.subscribe(SNAPSHOT_QUEUE, Lag::DropOldest);
+    let tracker = Arc::clone(&in_progress);
+    let _ = std::thread::spawn(move || tracker.follow(positions));
+    let mut signals = Signals::new([SIGINT, SIGTERM]).unwrap();
+    let _ = std::thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            match in_progress.save(Path::new(SNAPSHOT_FILE)) {
+                Ok(n) => println!("shutting down, games in progress saved: {}", n),
+                Err(e) => eprintln!("couldn't save games in progress: {}", e),
+            }
+            std::process::exit(0);
+        }
+    });
+}
+
+/// Switch maintenance mode on and off each time the server
+/// gets `SIGUSR1`.
+#[cfg(feature = "maintenance")]
+fn watch_maintenance() {
+    let mut signals = Signals::new([SIGUSR1]).unwrap();
+    let _ = std::thread::spawn(move || {
+        for _ in signals.forever() {
+            let on = !MAINTENANCE.fetch_xor(true, Ordering::Relaxed);
+            println!("maintenance mode {}", if on { "on" } else { "off" });
+        }
+    });
+}
+
+/// Tell a client connecting in maintenance mode to come
+/// back later. Games already under way carry on.
+#[cfg(feature = "maintenance")]
+fn turn_away(mut socket: TcpStream, message: &str) {
+    // A fresh socket takes a line without blocking, and the
+    // client going away is no loss.
+    let _ = writeln!(socket, "{}", message);
+}
+
+/// Open the game server's listening socket.
+#[cfg(feature = "tuning")]
+fn listen(config: &Config) -> Result<TcpListener, Error> {
+    let addr: SocketAddr = "127.0.0.1:10015".parse().unwrap();
+    let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(config.backlog)?;
+    Ok(socket.into())
+}
+
+/// Open the game server's listening socket, with the
+/// system's default backlog.
+#[cfg(not(feature = "tuning"))]
+fn listen(_config: &Config) -> Result<TcpListener, Error> {
+    TcpListener::bind("127.0.0.1:10015")
+}
+
+/// Apply the configured socket options to a client socket.
+fn tune(socket: &TcpStream, config: &Config) -> Result<(), Error> {
+    socket.set_nodelay(config.nodelay)?;
+    // A client that stops reading would otherwise block its
+    // game's writes forever.
+    socket.set_write_timeout(config.write_timeout)?;
+    // Prompts give up on a client that goes quiet this long.
+    socket.set_read_timeout(config.idle_timeout)?;
+    #[cfg(feature = "tuning")]
+    tune_more(socket, config)?;
+    Ok(())
+}
+
+/// Apply the socket options that need more than the
+/// standard library.
+#[cfg(feature = "tuning")]
+fn tune_more(socket: &TcpStream, config: &Config) -> Result<(), Error> {
+    let socket = SockRef::from(socket);
+    if let Some(time) = config.keepalive {
+        let mut keepalive = TcpKeepalive::new().with_time(time);
+        if let Some(interval) = config.keepalive_interval {
+            keepalive = keepalive.with_interval(interval);
+        }
+        socket.set_tcp_keepalive(&keepalive)?;
+    }
+    socket.set_linger(config.linger)?;
+    Ok(())
 }
 
 /// Listen for connections to the game server and start a
 /// new game for each.
 fn main() {
-    let listener = TcpListener::bind("127.0.0.1:10015").unwrap();
+    if std::env::args().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", config::USAGE);
+        return;
+    }
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", config::USAGE);
+            std::process::exit(2);
+        }
+    };
+    #[cfg(feature = "learning")]
+    let learner = match Learner::load(LEARNING_FILE) {
+        Ok(learner) => learner,
+        Err(e) => {
+            eprintln!("couldn't load learned values: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // Without the feature, learning lasts until the server
+    // stops.
+    #[cfg(not(feature = "learning"))]
+    let learner = Learner::default();
+    let learner = Arc::new(Mutex::new(learner));
+    NEXT_GAME_ID.store(random(), Ordering::Relaxed);
+    let access_log = config.access_log.as_ref().map(|path| {
+        match AccessLog::open(path, config.access_log_size) {
+            Ok(log) => Arc::new(Mutex::new(log)),
+            Err(e) => {
+                eprintln!("couldn't open access log: {}", e);
+                std::process::exit(1);
+            }
+        }
+    });
+    let motd = config.motd.as_ref().map(|path| match Motd::load(path) {
+        Ok(motd) => Arc::new(motd),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    });
+    let events = Arc::new(EventBus::new());
+    let log = events.subscribe(config.log_queue, config.log_lag);
+    let _ = std::thread::spawn(move || log_events(log));
+    let stats = Arc::new(Stats::new());
+    let finished = events.subscribe(STATS_QUEUE, Lag::DropOldest);
+    let counter = Arc::clone(&stats);
+    let _ = std::thread::spawn(move || counter.follow(finished));
+    #[cfg(feature = "snapshot")]
+    keep_games(&events);
+    #[cfg(feature = "maintenance")]
+    watch_maintenance();
+    let shared = Shared {
+        learner,
+        events,
+        stats,
+        motd,
+        hints: config.hints,
+    };
+    let listener = listen(&config).unwrap();
     loop {
         match listener.accept() {
             Ok((socket, addr)) => {
+                #[cfg(feature = "maintenance")]
+                if MAINTENANCE.load(Ordering::Relaxed) {
+                    println!("client {:?}: turned away for maintenance", addr);
+                    turn_away(socket, &config.maintenance_message);
+                    Visit::new(access_log.clone(), addr, None).ended("maintenance");
+                    continue;
+                }
                 println!("new client: {:?}", addr);
-                let _ = std::thread::spawn(move || {
-                    let reader = socket;
-                    let mut writer = reader.try_clone().unwrap();
-                    // https://stackoverflow.com/a/27841363
-                    writeln!(writer, "n15 {}", env!("CARGO_PKG_VERSION")).unwrap();
-                    let reader = BufReader::new(reader);
-                    game_loop(reader, writer).unwrap();
-                });
+                if let Err(e) = tune(&socket, &config) {
+                    println!("couldn't set socket options for {:?}: {:?}", addr, e);
+                }
+                let shared = shared.clone();
+                let mut visit = Visit::new(access_log.clone(), addr, config.max_bytes);
+                let _ = std::thread::spawn(move || serve(socket, &shared, &mut visit));
             }
             Err(e) => {
                 println!("couldn't get client: {:?}", e);
@@ -285,3 +1348,46 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    /// A client that never reads what it is sent is dropped
+    /// once writes to it time out, and logged as such.
+    #[test]
+    fn non_reading_client_times_out() {
+        let dir = std::env::temp_dir().join(format!("net15-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("access.log");
+        let shared = Shared {
+            learner: Arc::new(Mutex::new(Learner::load(dir.join("values.txt")).unwrap())),
+            events: Arc::new(EventBus::new()),
+            stats: Arc::new(Stats::new()),
+            motd: None,
+            hints: 3,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (socket, addr) = listener.accept().unwrap();
+        let config = Config {
+            write_timeout: Some(Duration::from_millis(200)),
+            ..Config::default()
+        };
+        tune(&socket, &config).unwrap();
+        // Keep asking for output without ever reading it.
+        let _ = std::thread::spawn(move || {
+            let _ = client.write_all(&b"verbose\n".repeat(1 << 20));
+        });
+
+        let log = AccessLog::open(&log_path, 1 << 20).unwrap();
+        let mut visit = Visit::new(Some(Arc::new(Mutex::new(log))), addr, None);
+        serve(socket, &shared, &mut visit);
+        drop(visit);
+        let entry = fs::read_to_string(&log_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(entry.trim_end().ends_with(" write timeout"), "{}", entry);
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Message of the day, shown to each client after the
+//! banner.
+//!
+//! The message is a template, filled in afresh for each
+//! connection. These variables are replaced by their values:
+//!
+//! * `{players_online}`: clients connected, this one included
+//! * `{games_today}`: games finished so far today (UTC)
+//! * `{uptime}`: how long the server has been running
+//! * `{time}`: the time of day, UTC
+//!
+//! Any other braces are left alone.
+
+use crate::stats::{self, Stats};
+
+use std::fs;
+use std::path::Path;
+
+/// Names of the variables a template may use.
+const VARIABLES: [&str; 4] = ["players_online", "games_today", "uptime", "time"];
+
+/// A message of the day template.
+pub struct Motd {
+    template: String,
+}
+
+/// Split `text` at its first `{name}` for a name that looks
+/// like a variable, giving the text before, the name and
+/// the text after.
+fn next_variable(text: &str) -> Option<(&str, &str, &str)> {
+    let mut from = 0;
+    while let Some(open) = text[from..].find('{').map(|i| from + i) {
+        let rest = &text[open + 1..];
+        if let Some(close) = rest.find('}') {
+            let name = &rest[..close];
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+                return Some((&text[..open], name, &rest[close + 1..]));
+            }
+        }
+        from = open + 1;
+    }
+    None
+}
+
+/// Write a duration as days, hours and minutes.
+fn duration(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+impl Motd {
+    /// Read a template from `path`, checking that it only
+    /// uses known variables.
+    pub fn load(path: &Path) -> Result<Motd, String> {
+        let template = fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        let mut rest = template.as_str();
+        while let Some((_, name, after)) = next_variable(rest) {
+            if !VARIABLES.contains(&name) {
+                return Err(format!("unknown motd variable {{{}}}", name));
+            }
+            rest = after;
+        }
+        Ok(Motd { template })
+    }
+
+    /// Fill in the template with the current statistics.
+    pub fn render(&self, stats: &Stats) -> String {
+        let mut out = String::new();
+        let mut rest = self.template.as_str();
+        while let Some((before, name, after)) = next_variable(rest) {
+            out += before;
+            match name {
+                "players_online" => out += &stats.online().to_string(),
+                "games_today" => out += &stats.games_today().to_string(),
+                "uptime" => out += &duration(stats.uptime().as_secs()),
+                "time" => {
+                    let secs = stats::now() % 86400;
+                    out += &format!("{:02}:{:02}", secs / 3600, secs / 60 % 60);
+                }
+                _ => unreachable!("variables are checked on load"),
+            }
+            rest = after;
+        }
+        out + rest
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Nim, as a side game.
+//!
+//! Players take turns removing any number of objects from
+//! one heap. In normal play whoever takes the last object
+//! wins; in misère play whoever takes it loses. The machine
+//! plays perfectly: in normal play it leaves heaps whose
+//! sizes XOR to zero, and in misère play it does the same
+//! until only one heap bigger than 1 is left, then leaves
+//! an odd number of single objects.
+
+use crate::prompt::{Answer, Prompt};
+
+use std::io::{BufRead, Error, ErrorKind, Write};
+
+/// Heaps used when none are given.
+const DEFAULT_HEAPS: [u64; 3] = [3, 4, 5];
+
+/// Most heaps allowed in a game.
+const MAX_HEAPS: usize = 9;
+
+/// Most objects allowed in a heap.
+const MAX_HEAP: u64 = 99;
+
+/// A game of Nim in progress.
+struct Nim {
+    heaps: Vec<u64>,
+    misere: bool,
+}
+
+impl Nim {
+    /// Set up a game from the words after `nim`: optionally
+    /// `misere`, then the heap sizes.
+    fn parse(args: &str) -> Result<Nim, &'static str> {
+        let mut words = args.split_whitespace().peekable();
+        let misere = words.next_if_eq(&"misere").is_some();
+        let mut heaps = Vec::new();
+        for word in words {
+            match word.parse::<u64>() {
+                Ok(n) if (1..=MAX_HEAP).contains(&n) => heaps.push(n),
+                _ => return Err("heaps are numbers from 1 to 99"),
+            }
+        }
+        if heaps.len() > MAX_HEAPS {
+            return Err("at most 9 heaps");
+        }
+        if heaps.is_empty() {
+            heaps = DEFAULT_HEAPS.to_vec();
+        }
+        Ok(Nim { heaps, misere })
+    }
+
+    /// Is every heap empty?
+    fn is_over(&self) -> bool {
+        self.heaps.iter().all(|&h| h == 0)
+    }
+
+    /// Pick a move as a heap index and a number to take:
+    /// a winning one if there is one, otherwise a single
+    /// object from the biggest heap.
+    fn choose(&self) -> (usize, u64) {
+        let big = self.heaps.iter().filter(|&&h| h > 1).count();
+        if self.misere && big == 1 {
+            let (i, &h) = self
+                .heaps
+                .iter()
+                .enumerate()
+                .find(|&(_, &h)| h > 1)
+                .unwrap();
+            let ones = self.heaps.iter().filter(|&&h| h == 1).count();
+            // Leave an odd number of single objects.
+            let keep = if ones % 2 == 0 { 1 } else { 0 };
+            return (i, h - keep);
+        }
+        let mut sum = self.heaps.iter().fold(0, |s, &h| s ^ h);
+        if self.misere && big == 0 {
+            // Only single objects left: what matters is
+            // whether there is an odd number of them.
+            sum = 1 - sum;
+        }
+        if sum != 0 {
+            for (i, &h) in self.heaps.iter().enumerate() {
+                if h ^ sum < h {
+                    return (i, h - (h ^ sum));
+                }
+            }
+        }
+        let (i, _) = self
+            .heaps
+            .iter()
+            .enumerate()
+            .max_by_key(|&(_, &h)| h)
+            .unwrap();
+        (i, 1)
+    }
+}
+
+/// A client's answer at the Nim move prompt.
+enum Move {
+    /// Take `n` from heap `i`, counting heaps from 1.
+    Take(usize, u64),
+    Quit,
+    Bad,
+}
+
+/// Decode a line, already trimmed, as a Nim move.
+fn decode_move(line: &str) -> Move {
+    if line == "quit" {
+        return Move::Quit;
+    }
+    let mut words = line.split_whitespace().map(|w| w.parse::<u64>());
+    match (words.next(), words.next(), words.next()) {
+        (Some(Ok(i)), Some(Ok(n)), None) => Move::Take(i as usize, n),
+        _ => Move::Bad,
+    }
+}
+
+/// Play a game of Nim with the client, set up from the
+/// words after `nim`. The client moves first.
+pub fn play(args: &str, reader: &mut dyn BufRead, writer: &mut dyn Write) -> Result<(), Error> {
+    let mut nim = match Nim::parse(args) {
+        Ok(nim) => nim,
+        Err(e) => {
+            writeln!(writer, "nim: {}", e)?;
+            return Ok(());
+        }
+    };
+    writeln!(writer)?;
+    if nim.misere {
+        writeln!(writer, "nim, misère: whoever takes the last one loses")?;
+    } else {
+        writeln!(writer, "nim: whoever takes the last one wins")?;
+    }
+    writeln!(writer, "move as <heap> <count>, or quit")?;
+    let mut human = true;
+    while !nim.is_over() {
+        writeln!(writer)?;
+        let heaps: Vec<String> = nim.heaps.iter().map(ToString::to_string).collect();
+        writeln!(writer, "heaps: {}", heaps.join(" "))?;
+        if !human {
+            let (i, n) = nim.choose();
+            nim.heaps[i] -= n;
+            writeln!(writer, "I take {} from heap {}", n, i + 1)?;
+            human = true;
+            continue;
+        }
+        let (i, n) = match Prompt::new("nim move: ").ask(reader, writer, decode_move)? {
+            Answer::Line(Move::Take(i, n)) => (i, n),
+            Answer::Line(Move::Quit) => {
+                writeln!(writer, "back to 15")?;
+                return Ok(());
+            }
+            Answer::Hangup => return Err(Error::from(ErrorKind::UnexpectedEof)),
+            Answer::Line(Move::Bad) | Answer::Garbled | Answer::TooLong => {
+                writeln!(writer, "move as <heap> <count>, or quit")?;
+                continue;
+            }
+        };
+        if i == 0 || i > nim.heaps.len() || n == 0 || n > nim.heaps[i - 1] {
+            writeln!(writer, "can't take that")?;
+            continue;
+        }
+        nim.heaps[i - 1] -= n;
+        human = false;
+    }
+    writeln!(writer)?;
+    // `human` says who is to move, so the other player took
+    // the last object.
+    let result = if human == nim.misere {
+        "you win"
+    } else {
+        "I win"
+    };
+    writeln!(writer, "{}", result)?;
+    writeln!(writer, "back to 15")?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Pacing for demo games and replays, so that they can be
+//! followed as they are played, say on a projector in a
+//! lecture. The client sets it with the `pace` command; live
+//! games are never paced.
+
+use crate::terminal::switch;
+
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Pause after each character typed out by the typewriter.
+const TYPEWRITER_DELAY: Duration = Duration::from_millis(20);
+
+/// Longest pause between moves a client may ask for, in
+/// seconds.
+const MAX_DELAY: f64 = 10.0;
+
+/// How demo games and replays are paced.
+#[derive(Clone, Copy, Default)]
+pub struct Pacing {
+    /// Pause between moves, if set. Otherwise demo games
+    /// pause for a second and replays not at all.
+    pub delay: Option<Duration>,
+    /// Type output out a character at a time.
+    pub typewriter: bool,
+}
+
+impl Display for Pacing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.delay {
+            Some(delay) => write!(f, "delay {:.1}s", delay.as_secs_f64())?,
+            None => write!(f, "delay default")?,
+        }
+        let typewriter = if self.typewriter { "on" } else { "off" };
+        write!(f, ", typewriter {}", typewriter)
+    }
+}
+
+impl Pacing {
+    /// Change a setting as asked by a `pace` command, given
+    /// the words after `pace`.
+    pub fn configure(&mut self, args: &str) -> Result<(), &'static str> {
+        let mut words = args.split_whitespace();
+        match words.next() {
+            Some("delay") => match words.next() {
+                Some("default") => self.delay = None,
+                delay => match delay.and_then(|d| d.parse::<f64>().ok()) {
+                    Some(delay) if (0.0..=MAX_DELAY).contains(&delay) => {
+                        self.delay = Some(Duration::from_secs_f64(delay));
+                    }
+                    _ => return Err("delay must be default or 0 to 10 seconds"),
+                },
+            },
+            Some("typewriter") => self.typewriter = switch(words.next())?,
+            Some("default") => *self = Pacing::default(),
+            _ => return Err("settings: delay <secs>|default, typewriter on|off, default"),
+        }
+        if words.next().is_some() {
+            return Err("too many arguments");
+        }
+        Ok(())
+    }
+
+    /// Pause between moves, for `default` if no delay is set.
+    pub fn pause(&self, default: Duration) {
+        std::thread::sleep(self.delay.unwrap_or(default));
+    }
+
+    /// Wrap `writer` so that output goes out at this pace.
+    pub fn writer<'a>(&self, writer: &'a mut dyn Write) -> Paced<'a> {
+        Paced {
+            inner: writer,
+            typewriter: self.typewriter,
+        }
+    }
+}
+
+/// A writer that types its output out if asked to.
+pub struct Paced<'a> {
+    inner: &'a mut dyn Write,
+    typewriter: bool,
+}
+
+impl Write for Paced<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        if !self.typewriter {
+            return self.inner.write(buf);
+        }
+        for (i, &b) in buf.iter().enumerate() {
+            self.inner.write_all(&[b])?;
+            // Pause only once a whole character is out.
+            let continues = buf.get(i + 1).is_some_and(|&next| next & 0xc0 == 0x80);
+            if b != b'\n' && !continues {
+                self.inner.flush()?;
+                std::thread::sleep(TYPEWRITER_DELAY);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Personas for the machine, one per difficulty.
+//!
+//! A persona has a name and a few things to say: a greeting
+//! when a game starts, remarks now and then as it plays,
+//! and a line for however the game ends. What it says is a
+//! reaction to game events, and the lines themselves live
+//! in `personas.txt`.
+
+use crate::events::GameEvent;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use std::collections::HashMap;
+
+/// Chance of a remark after each machine move.
+const TALK_CHANCE: f64 = 0.25;
+
+/// A name and the lines that go with it.
+#[derive(Default)]
+struct Persona {
+    name: String,
+    greeting: Vec<String>,
+    talk: Vec<String>,
+    win: Vec<String>,
+    loss: Vec<String>,
+    draw: Vec<String>,
+}
+
+/// The personas, by difficulty name.
+pub struct Personas {
+    personas: HashMap<String, Persona>,
+}
+
+impl Personas {
+    /// The personas shipped with the server.
+    pub fn standard() -> Personas {
+        Personas::parse(include_str!("personas.txt")).unwrap()
+    }
+
+    /// Parse personas from their text form.
+    pub fn parse(text: &str) -> Result<Personas, String> {
+        let mut personas: HashMap<String, Persona> = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |msg| format!("persona line {}: {}", i + 1, msg);
+            let mut fields = line.splitn(3, ' ');
+            let (difficulty, kind, text) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(d), Some(k), Some(t)) => (d, k, t.trim().to_string()),
+                _ => return Err(err("expected difficulty, kind and text")),
+            };
+            let persona = personas.entry(difficulty.to_string()).or_default();
+            match kind {
+                "name" if persona.name.is_empty() => persona.name = text,
+                "name" => return Err(err("second name")),
+                "greeting" => persona.greeting.push(text),
+                "talk" => persona.talk.push(text),
+                "win" => persona.win.push(text),
+                "loss" => persona.loss.push(text),
+                "draw" => persona.draw.push(text),
+                _ => return Err(err("unknown kind")),
+            }
+        }
+        if let Some(difficulty) = personas.keys().find(|d| personas[*d].name.is_empty()) {
+            return Err(format!("persona for {} has no name", difficulty));
+        }
+        Ok(Personas { personas })
+    }
+
+    /// What the persona for `difficulty` has to say about
+    /// `event`, if anything, as a line ready to show. The
+    /// persona speaks for the player named `machine`.
+    pub fn react<R: Rng>(
+        &self,
+        difficulty: &str,
+        event: &GameEvent,
+        machine: &str,
+        rng: &mut R,
+    ) -> Option<String> {
+        let persona = self.personas.get(difficulty)?;
+        let lines = match event {
+            GameEvent::Started { .. } => &persona.greeting,
+            GameEvent::Moved { player, .. } if *player == machine && rng.gen_bool(TALK_CHANCE) => {
+                &persona.talk
+            }
+            GameEvent::Finished {
+                winner: Some(winner),
+                ..
+            } if winner == machine => &persona.win,
+            GameEvent::Finished {
+                winner: Some(_), ..
+            } => &persona.loss,
+            GameEvent::Finished { winner: None, .. } => &persona.draw,
+            _ => return None,
+        };
+        let line = lines.choose(rng)?;
+        Some(format!("{}: \"{}\"", persona.name, line))
+    }
+}
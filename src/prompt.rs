@@ -0,0 +1,163 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Asking the client for a line of input.
+//!
+//! Every prompt works the same way: write the prompt, flush
+//! it, read a line and make sense of it. A `Prompt` does all
+//! of that, so that each caller only decodes the line and
+//! handles the few ways reading one can go wrong. Lines are
+//! bounded in length, and a client that stays silent past
+//! the socket's read timeout is dropped.
+
+use std::io::{BufRead, Error, ErrorKind, Write};
+
+/// Longest line a client may send, in bytes. The rest of a
+/// longer line is read and thrown away.
+const MAX_LINE: usize = 1024;
+
+/// What came of asking, with the line decoded to `T`.
+pub enum Answer<T> {
+    /// A line, trimmed and decoded.
+    Line(T),
+    /// The client closed the connection.
+    Hangup,
+    /// The line was not valid text.
+    Garbled,
+    /// The line was longer than the client may send.
+    TooLong,
+}
+
+/// A line read from the client, before decoding.
+enum Raw {
+    Line(Vec<u8>),
+    Hangup,
+    TooLong,
+}
+
+/// Read a line of at most `MAX_LINE` bytes.
+fn read_line(reader: &mut dyn BufRead) -> Result<Raw, Error> {
+    let mut line = Vec::new();
+    let mut too_long = false;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            if line.is_empty() && !too_long {
+                return Ok(Raw::Hangup);
+            }
+            break;
+        }
+        let (len, done) = match buf.iter().position(|&b| b == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (buf.len(), false),
+        };
+        if line.len() + len > MAX_LINE + 1 {
+            too_long = true;
+        } else {
+            line.extend_from_slice(&buf[..len]);
+        }
+        reader.consume(len);
+        if done {
+            break;
+        }
+    }
+    if too_long {
+        return Ok(Raw::TooLong);
+    }
+    Ok(Raw::Line(line))
+}
+
+/// A prompt to show the client.
+pub struct Prompt<'a> {
+    text: &'a str,
+}
+
+impl<'a> Prompt<'a> {
+    /// A prompt showing `text`.
+    pub fn new(text: &'a str) -> Prompt<'a> {
+        Prompt { text }
+    }
+
+    /// Show the prompt and read the client's answer,
+    /// decoding the trimmed line with `decode`. A client that
+    /// lets the read time out is told so and dropped.
+    pub fn ask<T, F>(
+        &self,
+        reader: &mut dyn BufRead,
+        writer: &mut dyn Write,
+        decode: F,
+    ) -> Result<Answer<T>, Error>
+    where
+        F: FnOnce(&str) -> T,
+    {
+        write!(writer, "{}", self.text)?;
+        writer.flush()?;
+        let raw = match read_line(reader) {
+            Ok(raw) => raw,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                // The client may be gone for good; no matter.
+                let _ = writeln!(writer);
+                let _ = writeln!(writer, "idle too long, goodbye");
+                return Err(Error::other("idle too long"));
+            }
+            Err(e) => return Err(e),
+        };
+        let line = match raw {
+            Raw::Line(line) => line,
+            Raw::Hangup => return Ok(Answer::Hangup),
+            Raw::TooLong => return Ok(Answer::TooLong),
+        };
+        match String::from_utf8(line) {
+            Ok(line) => Ok(Answer::Line(decode(line.trim()))),
+            Err(_) => Ok(Answer::Garbled),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::BufReader;
+
+    #[test]
+    fn overlong_line_is_thrown_away() {
+        let mut text = vec![b'x'; 3 * MAX_LINE];
+        text.extend_from_slice(b"\n42\n");
+        // A small buffer makes the long line span many reads.
+        let mut reader = BufReader::with_capacity(16, &text[..]);
+        assert!(matches!(read_line(&mut reader), Ok(Raw::TooLong)));
+        assert!(matches!(read_line(&mut reader), Ok(Raw::Line(l)) if l == b"42\n"));
+        assert!(matches!(read_line(&mut reader), Ok(Raw::Hangup)));
+    }
+
+    #[test]
+    fn longest_line_is_kept() {
+        let mut text = vec![b'x'; MAX_LINE];
+        text.push(b'\n');
+        let mut reader = BufReader::with_capacity(16, &text[..]);
+        assert!(matches!(read_line(&mut reader), Ok(Raw::Line(l)) if l.len() == MAX_LINE + 1));
+    }
+
+    #[test]
+    fn hangup() {
+        let mut reader: &[u8] = b"";
+        let mut out = Vec::new();
+        let answer = Prompt::new("move: ").ask(&mut reader, &mut out, str::to_string);
+        assert!(matches!(answer, Ok(Answer::Hangup)));
+        assert_eq!(out, b"move: ");
+    }
+
+    #[test]
+    fn invalid_utf8_is_garbled() {
+        let mut reader: &[u8] = b"\xff\xfe\n5\n";
+        let mut out = Vec::new();
+        let prompt = Prompt::new("move: ");
+        let answer = prompt.ask(&mut reader, &mut out, str::to_string);
+        assert!(matches!(answer, Ok(Answer::Garbled)));
+        let answer = prompt.ask(&mut reader, &mut out, str::to_string);
+        assert!(matches!(answer, Ok(Answer::Line(l)) if l == "5"));
+    }
+}
@@ -0,0 +1,311 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! The text protocol spoken with clients.
+//!
+//! A client is always at one of a few prompts, each a
+//! `State` with its own type of input. Each line the client
+//! sends is decoded into the input type of the prompt it
+//! answers, so that the server acts on what the client asked
+//! for rather than on raw text, and only has to handle the
+//! inputs that make sense at that prompt. Inputs that can
+//! come at any prompt are an `AnyInput`. At the rematch
+//! prompt anything not understood declines the rematch,
+//! while at the move prompt it is an error.
+
+use crate::prompt::{Answer, Prompt};
+use crate::{Difficulty, BOTS};
+
+use std::io::{BufRead, Error, Write};
+
+/// Complaint about input that isn't understood.
+const BAD_CHOICE: &str = "bad choice try again";
+
+/// A prompt a client can be at, named by the input that
+/// answers it.
+pub trait State: From<AnyInput> {
+    /// Text of the prompt.
+    const PROMPT: &'static str;
+
+    /// Decode a line, already trimmed, that is none of the
+    /// inputs understood at any prompt.
+    fn decode(line: &str) -> Self;
+}
+
+/// What the client can ask for at any prompt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnyInput {
+    /// The client closed the connection.
+    Hangup,
+    /// The line was not valid text.
+    Garbled,
+    /// The line was not understood; say this and ask again.
+    Invalid(String),
+    Export,
+    Demo,
+    /// Play Nim with these arguments.
+    Nim(String),
+    /// Play through a game record, grading the moves if
+    /// `analyze` is set.
+    Replay {
+        record: String,
+        analyze: bool,
+    },
+}
+
+/// What the client asked for on their turn in a game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveInput {
+    /// Take this number.
+    Take(u64),
+    Save,
+    Load(String),
+    #[cfg(feature = "snapshot")]
+    Resume(String),
+    Hint,
+    /// Show or change terminal settings with these
+    /// arguments.
+    Term(String),
+    /// Show or change demo and replay pacing with these
+    /// arguments.
+    Pace(String),
+    Grid,
+    Verbose,
+    HandicapCenter,
+    HandicapGive(u64),
+    Duplicates,
+    Mystery,
+    Players(usize),
+    Difficulty(Difficulty),
+    Any(AnyInput),
+}
+
+/// What the client asked for once a game is over.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RematchInput {
+    /// Play another game.
+    Rematch,
+    /// Decline another game.
+    Quit,
+    Achievements,
+    Any(AnyInput),
+}
+
+impl From<AnyInput> for MoveInput {
+    fn from(input: AnyInput) -> Self {
+        MoveInput::Any(input)
+    }
+}
+
+impl From<AnyInput> for RematchInput {
+    fn from(input: AnyInput) -> Self {
+        RematchInput::Any(input)
+    }
+}
+
+impl State for MoveInput {
+    const PROMPT: &'static str = "move: ";
+
+    fn decode(line: &str) -> Self {
+        decode_move(line)
+    }
+}
+
+impl State for RematchInput {
+    const PROMPT: &'static str = "play again? ";
+
+    fn decode(line: &str) -> Self {
+        match line {
+            "y" | "yes" => RematchInput::Rematch,
+            "achievements" => RematchInput::Achievements,
+            _ => RematchInput::Quit,
+        }
+    }
+}
+
+/// Prompt the client for the input of state `S` and decode
+/// their answer.
+pub fn read<S: State>(reader: &mut dyn BufRead, writer: &mut dyn Write) -> Result<S, Error> {
+    let answer = Prompt::new(S::PROMPT).ask(reader, writer, decode::<S>)?;
+    let input = match answer {
+        Answer::Line(input) => return Ok(input),
+        Answer::Hangup => AnyInput::Hangup,
+        Answer::Garbled => AnyInput::Garbled,
+        Answer::TooLong => AnyInput::Invalid("line too long".to_string()),
+    };
+    Ok(input.into())
+}
+
+/// Decode a line, already trimmed, answering the prompt for
+/// state `S`.
+pub fn decode<S: State>(line: &str) -> S {
+    match decode_anywhere(line) {
+        Some(input) => input.into(),
+        None => S::decode(line),
+    }
+}
+
+/// Decode the inputs that work at any prompt.
+fn decode_anywhere(line: &str) -> Option<AnyInput> {
+    let input = match line {
+        "export" => AnyInput::Export,
+        "demo" => AnyInput::Demo,
+        _ if line.split_whitespace().next() == Some("nim") => AnyInput::Nim(line[3..].to_string()),
+        _ => {
+            let (record, analyze) = if let Some(record) = line.strip_prefix("replay ") {
+                (record, false)
+            } else if let Some(record) = line.strip_prefix("analyze ") {
+                (record, true)
+            } else {
+                return None;
+            };
+            AnyInput::Replay {
+                record: record.to_string(),
+                analyze,
+            }
+        }
+    };
+    Some(input)
+}
+
+/// Decode an answer to the move prompt.
+fn decode_move(line: &str) -> MoveInput {
+    let invalid = |msg: &str| MoveInput::Any(AnyInput::Invalid(msg.to_string()));
+    match line {
+        "save" => return MoveInput::Save,
+        "hint" => return MoveInput::Hint,
+        "grid" => return MoveInput::Grid,
+        "verbose" => return MoveInput::Verbose,
+        "handicap center" => return MoveInput::HandicapCenter,
+        "duplicates" => return MoveInput::Duplicates,
+        "mystery" => return MoveInput::Mystery,
+        _ => (),
+    }
+    if let Some(args) = line.strip_prefix("term") {
+        if !args.is_empty() && !args.starts_with(' ') {
+            return invalid(BAD_CHOICE);
+        }
+        return MoveInput::Term(args.to_string());
+    }
+    if let Some(args) = line.strip_prefix("pace") {
+        if !args.is_empty() && !args.starts_with(' ') {
+            return invalid(BAD_CHOICE);
+        }
+        return MoveInput::Pace(args.to_string());
+    }
+    if let Some(n) = line.strip_prefix("handicap give ") {
+        return match n.trim().parse::<u64>() {
+            Ok(n) => MoveInput::HandicapGive(n),
+            Err(_) => invalid(BAD_CHOICE),
+        };
+    }
+    if let Some(n) = line.strip_prefix("players ") {
+        return match n.trim().parse::<usize>() {
+            Ok(n) if (2..=2 + BOTS.len()).contains(&n) => MoveInput::Players(n),
+            _ => invalid(&format!("players: 2 to {}", 2 + BOTS.len())),
+        };
+    }
+    if let Some(level) = line.strip_prefix("difficulty ") {
+        return match level.trim() {
+            "normal" => MoveInput::Difficulty(Difficulty::Normal),
+            "learning" => MoveInput::Difficulty(Difficulty::Learning),
+            "perfect" => MoveInput::Difficulty(Difficulty::Perfect),
+            _ => invalid("difficulties: normal learning perfect"),
+        };
+    }
+    #[cfg(feature = "snapshot")]
+    if let Some(game) = line.strip_prefix("resume ") {
+        return MoveInput::Resume(game.trim().to_string());
+    }
+    if let Some(code) = line.strip_prefix("load ") {
+        return MoveInput::Load(code.trim().to_string());
+    }
+    match line.parse::<u64>() {
+        Ok(n) => MoveInput::Take(n),
+        Err(_) => invalid(BAD_CHOICE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(msg: &str) -> MoveInput {
+        MoveInput::Any(AnyInput::Invalid(msg.to_string()))
+    }
+
+    fn replay(record: &str, analyze: bool) -> AnyInput {
+        AnyInput::Replay {
+            record: record.to_string(),
+            analyze,
+        }
+    }
+
+    #[test]
+    fn move_lines_decode() {
+        let players = format!("players: 2 to {}", 2 + BOTS.len());
+        let difficulties = "difficulties: normal learning perfect";
+        let cases = [
+            ("7", MoveInput::Take(7)),
+            ("seven", invalid(BAD_CHOICE)),
+            ("hint", MoveInput::Hint),
+            ("term", MoveInput::Term(String::new())),
+            ("term width 60", MoveInput::Term(" width 60".to_string())),
+            ("termx", invalid(BAD_CHOICE)),
+            ("pacex", invalid(BAD_CHOICE)),
+            ("players 2", MoveInput::Players(2)),
+            ("players 4", invalid(&players)),
+            ("players two", invalid(&players)),
+            ("handicap give 9", MoveInput::HandicapGive(9)),
+            ("handicap give x", invalid(BAD_CHOICE)),
+            (
+                "difficulty perfect",
+                MoveInput::Difficulty(Difficulty::Perfect),
+            ),
+            ("difficulty bogus", invalid(difficulties)),
+            ("load  q704 ", MoveInput::Load("q704".to_string())),
+            ("nim", MoveInput::Any(AnyInput::Nim(String::new()))),
+            ("nim 3 4", MoveInput::Any(AnyInput::Nim(" 3 4".to_string()))),
+            ("nimble", invalid(BAD_CHOICE)),
+            ("replay 1. 5 *", MoveInput::Any(replay("1. 5 *", false))),
+            ("analyze 1. 5 *", MoveInput::Any(replay("1. 5 *", true))),
+            ("replay", invalid(BAD_CHOICE)),
+            ("export", MoveInput::Any(AnyInput::Export)),
+        ];
+        for (line, input) in cases {
+            assert_eq!(decode::<MoveInput>(line), input, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn rematch_lines_decode() {
+        let cases = [
+            ("y", RematchInput::Rematch),
+            ("yes", RematchInput::Rematch),
+            ("achievements", RematchInput::Achievements),
+            ("nim 3", RematchInput::Any(AnyInput::Nim(" 3".to_string()))),
+            ("analyze 1. 5 *", RematchInput::Any(replay("1. 5 *", true))),
+            ("7", RematchInput::Quit),
+            ("", RematchInput::Quit),
+        ];
+        for (line, input) in cases {
+            assert_eq!(decode::<RematchInput>(line), input, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn hangup_and_overlong_lines_are_read() {
+        let mut out = Vec::new();
+        let mut reader: &[u8] = b"";
+        let input = read::<MoveInput>(&mut reader, &mut out).unwrap();
+        assert_eq!(input, MoveInput::Any(AnyInput::Hangup));
+        let mut text = vec![b'1'; 2000];
+        text.push(b'\n');
+        let input = read::<RematchInput>(&mut &text[..], &mut out).unwrap();
+        let too_long = AnyInput::Invalid("line too long".to_string());
+        assert_eq!(input, RematchInput::Any(too_long));
+        assert_eq!(out, b"move: play again? ");
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Game records in a compact text notation, loosely
+//! modeled on chess PGN.
+//!
+//! A record is a list of tags followed by the moves and the
+//! result:
+//!
+//! ```text
+//! [First "you"]
+//! [Second "I"]
+//! 1. 4 5 2. 6 2 3. 8 9 1/2-1/2
+//! ```
+//!
+//! Tags have the form `[Name "value"]`. `First` and
+//! `Second` name the players in the order they move. An
+//! optional `Start` tag gives the numbers each player
+//! already holds when the record begins, as `first/second`
+//! with the numbers written as digits (`[Start "5/2"]`);
+//! without it the game starts from nothing. An optional
+//! `Target` tag gives the sum to make when it isn't 15
+//! (`[Target "13"]`). Any other tags are kept but have no
+//! meaning to the notation; the server adds `Game`,
+//! `Hints` and `Times`, the seconds each move took
+//! (`[Times "4.2 0.0 7.9"]`). Missing player names default
+//! to `first` and `second`.
+//!
+//! Moves are the numbers taken, alternating between the
+//! players starting with `First`. Move numbers such as `1.`
+//! are optional. The result is `1-0` if `First` won, `0-1`
+//! if `Second` won, `1/2-1/2` for a draw and `*` for an
+//! unfinished game. Line breaks are not significant, so a
+//! record can be given on a single line.
+
+use crate::{Numbers, TARGET, TARGETS};
+
+use std::fmt::{self, Display};
+
+/// How a recorded game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    FirstWins,
+    SecondWins,
+    Draw,
+    Unfinished,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let token = match self {
+            Outcome::FirstWins => "1-0",
+            Outcome::SecondWins => "0-1",
+            Outcome::Draw => "1/2-1/2",
+            Outcome::Unfinished => "*",
+        };
+        write!(f, "{}", token)
+    }
+}
+
+/// A game record.
+#[derive(Clone)]
+pub struct Record {
+    /// Names of the first and second players to move.
+    pub players: [String; 2],
+    /// Numbers held by the first and second players when
+    /// the record begins.
+    pub start: [Numbers; 2],
+    /// The sum to make.
+    pub target: u64,
+    /// Numbers taken, in order.
+    pub moves: Vec<u64>,
+    pub outcome: Outcome,
+    /// Tags with no meaning to the notation.
+    pub tags: Tags,
+}
+
+impl Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[First {:?}]", self.players[0])?;
+        writeln!(f, "[Second {:?}]", self.players[1])?;
+        if !self.start[0].is_empty() || !self.start[1].is_empty() {
+            let [first, second] = &self.start;
+            writeln!(f, "[Start \"{}/{}\"]", first.digits(), second.digits())?;
+        }
+        if self.target != TARGET {
+            writeln!(f, "[Target \"{}\"]", self.target)?;
+        }
+        for (name, value) in &self.tags {
+            writeln!(f, "[{} {:?}]", name, value)?;
+        }
+        for (i, n) in self.moves.iter().enumerate() {
+            if i % 2 == 0 {
+                write!(f, "{}. ", i / 2 + 1)?;
+            }
+            write!(f, "{} ", n)?;
+        }
+        write!(f, "{}", self.outcome)
+    }
+}
+
+/// Tag names and values, in order.
+type Tags = Vec<(String, String)>;
+
+/// Split a record into tags and movetext tokens.
+fn tokenize(text: &str) -> Result<(Tags, Vec<&str>), String> {
+    let mut tags = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(tag) = rest.strip_prefix('[') {
+        let (tag, after) = tag.split_once(']').ok_or("unterminated tag")?;
+        let (name, value) = tag
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or("bad tag")?;
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .ok_or("tag value must be quoted")?;
+        tags.push((name.to_string(), value.to_string()));
+        rest = after.trim_start();
+    }
+    Ok((tags, rest.split_whitespace().collect()))
+}
+
+impl Record {
+    /// Start a record of a game between the named players,
+    /// who already hold the given numbers.
+    pub fn new(first: &str, second: &str, start: [Numbers; 2]) -> Record {
+        Record {
+            players: [first.to_string(), second.to_string()],
+            start,
+            target: TARGET,
+            moves: Vec::new(),
+            outcome: Outcome::Unfinished,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Begin the record again from a new position, keeping
+    /// its target and tags.
+    pub fn restart(&mut self, first: &str, second: &str, start: [Numbers; 2]) {
+        let tags = std::mem::take(&mut self.tags);
+        let target = self.target;
+        *self = Record::new(first, second, start);
+        self.target = target;
+        self.tags = tags;
+    }
+
+    /// Parse a record, checking that it describes a legal
+    /// game with the result it claims.
+    pub fn parse(text: &str) -> Result<Record, String> {
+        let (tags, tokens) = tokenize(text)?;
+        let mut record = Record::new("first", "second", [Numbers::new(), Numbers::new()]);
+        for (name, value) in tags {
+            match name.as_str() {
+                "First" => record.players[0] = value,
+                "Second" => record.players[1] = value,
+                "Start" => {
+                    let (first, second) = value.split_once('/').ok_or("bad Start tag")?;
+                    let first = Numbers::from_digits(first).ok_or("bad Start tag")?;
+                    let second = Numbers::from_digits(second).ok_or("bad Start tag")?;
+                    record.start = [first, second];
+                }
+                "Target" => match value.parse::<u64>() {
+                    Ok(target) if TARGETS.contains(&target) => record.target = target,
+                    _ => return Err("bad Target tag".to_string()),
+                },
+                _ => record.tags.push((name, value)),
+            }
+        }
+
+        let (result, moves) = tokens.split_last().ok_or("missing result")?;
+        record.outcome = match *result {
+            "1-0" => Outcome::FirstWins,
+            "0-1" => Outcome::SecondWins,
+            "1/2-1/2" => Outcome::Draw,
+            "*" => Outcome::Unfinished,
+            _ => return Err(format!("bad result {:?}", result)),
+        };
+        for token in moves {
+            if let Some(number) = token.strip_suffix('.') {
+                if number.parse::<usize>().is_err() {
+                    return Err(format!("bad move number {:?}", token));
+                }
+                continue;
+            }
+            let n = token
+                .parse::<u64>()
+                .map_err(|_| format!("bad move {:?}", token))?;
+            record.moves.push(n);
+        }
+
+        let played = record.replay()?;
+        if played != record.outcome {
+            return Err(format!("result should be {}", played));
+        }
+        Ok(record)
+    }
+
+    /// Play through the record, returning how the game
+    /// actually stands at the end.
+    pub fn replay(&self) -> Result<Outcome, String> {
+        let mut hands = self.start.clone();
+        let mut board = Numbers::new();
+        for n in 1..=9 {
+            if !hands[0].contains(n) && !hands[1].contains(n) {
+                board.insert(n);
+            }
+        }
+        if board.len() + hands[0].len() + hands[1].len() != 9 {
+            return Err("players share a number".to_string());
+        }
+        if hands[0].won(self.target).is_some() || hands[1].won(self.target).is_some() {
+            return Err("game is over before it starts".to_string());
+        }
+        for (i, &n) in self.moves.iter().enumerate() {
+            if board.is_empty() || hands[(i + 1) % 2].won(self.target).is_some() {
+                return Err(format!("move {} after the game is over", i + 1));
+            }
+            if !board.remove(n) {
+                return Err(format!("move {}: {} is not available", i + 1, n));
+            }
+            hands[i % 2].insert(n);
+        }
+        Ok(if hands[0].won(self.target).is_some() {
+            Outcome::FirstWins
+        } else if hands[1].won(self.target).is_some() {
+            Outcome::SecondWins
+        } else if board.is_empty() {
+            Outcome::Draw
+        } else {
+            Outcome::Unfinished
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_then_parse_round_trips() {
+        let mut record = Record::new(
+            "you",
+            "I",
+            [
+                Numbers::from_digits("5").unwrap(),
+                Numbers::from_digits("2").unwrap(),
+            ],
+        );
+        record.target = 14;
+        record.moves = vec![3, 6];
+        record.tags.push(("Game".to_string(), "00002a".to_string()));
+        let text = record.to_string();
+        assert!(text.contains("[Start \"5/2\"]"));
+        assert!(text.contains("[Target \"14\"]"));
+        let parsed = Record::parse(&text).unwrap();
+        assert_eq!(parsed.players, record.players);
+        assert_eq!(parsed.start, record.start);
+        assert_eq!(parsed.target, 14);
+        assert_eq!(parsed.moves, vec![3, 6]);
+        assert_eq!(parsed.outcome, Outcome::Unfinished);
+        assert_eq!(parsed.tags, record.tags);
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn finished_game_parses() {
+        let record = Record::parse("1. 4 1 2. 5 2 3. 6 1-0").unwrap();
+        assert_eq!(record.outcome, Outcome::FirstWins);
+        assert_eq!(record.players, ["first", "second"]);
+    }
+
+    #[test]
+    fn bad_records_are_rejected() {
+        let bad = [
+            ("1. 5 1 2-0", "bad result"),
+            ("1. 5 1 1-0", "result should be *"),
+            ("1. 4 1 2. 5 2 3. 6 3 1-0", "after the game is over"),
+            ("1. 5 5 *", "not available"),
+            ("[First you] 1. 5 *", "must be quoted"),
+            ("[Start \"5/5\"] *", "share a number"),
+            ("[Start \"12/x\"] *", "bad Start tag"),
+            ("[Target \"30\"] *", "bad Target tag"),
+            ("1. 10 *", "not available"),
+            ("", "missing result"),
+        ];
+        for (text, why) in bad {
+            match Record::parse(text) {
+                Ok(_) => panic!("{:?} parsed", text),
+                Err(e) => assert!(e.contains(why), "{:?}: {}", text, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Save codes for pausing and resuming a game against the
+//! machine.
+//!
+//! A position is encoded as a base-3 number with one digit
+//! per pool number `1..=9` (0 available, 1 human, 2
+//! machine), plus a turn bit on top. That fits in three
+//! base-36 digits; a fourth check digit catches most typos.
+//! A game played to a sum other than 15 adds the sum as one
+//! more digit before the check digit. A game where the human
+//! was handed a number as a handicap adds the sum, whatever
+//! it is, and then that number.
+
+use crate::{Numbers, TARGET, TARGETS};
+
+/// Number of digits in a save code, including the check
+/// digit, for a game played to 15.
+const CODE_LEN: usize = 4;
+
+/// Number of distinct positions, ignoring whose turn it is.
+const POSITIONS: u32 = 19683; // 3^9
+
+/// Whose turn it is in a saved game.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mover {
+    Human,
+    Machine,
+}
+
+/// Everything needed to recreate a game in progress.
+pub struct SavedGame {
+    pub board: Numbers,
+    pub human: Numbers,
+    pub machine: Numbers,
+    pub mover: Mover,
+    /// The sum to make.
+    pub target: u64,
+    /// Number the human was handed as a handicap, if any.
+    pub given: Option<u64>,
+}
+
+/// Check digit over the value digits of a code, weighted
+/// by position so that transpositions are caught too.
+fn check_digit(digits: &[u32]) -> u32 {
+    digits
+        .iter()
+        .enumerate()
+        .map(|(i, d)| (i as u32 + 1) * d)
+        .sum::<u32>()
+        % 36
+}
+
+impl SavedGame {
+    /// Produce the save code for this game.
+    pub fn encode(&self) -> String {
+        let mut value = 0;
+        for n in (1..=9).rev() {
+            let owner = if self.human.contains(n) {
+                1
+            } else if self.machine.contains(n) {
+                2
+            } else {
+                0
+            };
+            value = value * 3 + owner;
+        }
+        if self.mover == Mover::Machine {
+            value += POSITIONS;
+        }
+        let mut digits = Vec::with_capacity(CODE_LEN);
+        for _ in 0..CODE_LEN - 1 {
+            digits.push(value % 36);
+            value /= 36;
+        }
+        if self.given.is_some() || self.target != TARGET {
+            digits.push(self.target as u32);
+        }
+        if let Some(n) = self.given {
+            digits.push(n as u32);
+        }
+        digits.push(check_digit(&digits));
+        digits
+            .into_iter()
+            .map(|d| std::char::from_digit(d, 36).unwrap())
+            .collect()
+    }
+
+    /// Recreate a game from a save code. Codes come straight
+    /// off the wire, so anything that does not describe a
+    /// reachable, unfinished game is rejected.
+    pub fn decode(code: &str) -> Result<SavedGame, &'static str> {
+        let digits: Option<Vec<u32>> = code.chars().map(|c| c.to_digit(36)).collect();
+        let digits = match digits {
+            Some(digits) if (CODE_LEN..=CODE_LEN + 2).contains(&digits.len()) => digits,
+            _ => return Err("malformed code"),
+        };
+        let (check, digits) = digits.split_last().unwrap();
+        if check_digit(digits) != *check {
+            return Err("check digit mismatch");
+        }
+        let (value, extra) = digits.split_at(CODE_LEN - 1);
+        let (target, given) = match *extra {
+            [] => (TARGET, None),
+            [t] if t as u64 != TARGET && TARGETS.contains(&(t as u64)) => (t as u64, None),
+            [t, n] if TARGETS.contains(&(t as u64)) => match n {
+                1..=9 => (t as u64, Some(n as u64)),
+                _ => return Err("no such handicap"),
+            },
+            _ => return Err("no such target"),
+        };
+        let mut value = value.iter().rev().fold(0, |v, d| v * 36 + d);
+        if value >= 2 * POSITIONS {
+            return Err("no such position");
+        }
+        let mover = if value >= POSITIONS {
+            value -= POSITIONS;
+            Mover::Machine
+        } else {
+            Mover::Human
+        };
+
+        let mut saved = SavedGame {
+            board: Numbers::new(),
+            human: Numbers::new(),
+            machine: Numbers::new(),
+            mover,
+            target,
+            given,
+        };
+        for n in 1..=9 {
+            match value % 3 {
+                0 => saved.board.insert(n),
+                1 => saved.human.insert(n),
+                _ => saved.machine.insert(n),
+            }
+            value /= 3;
+        }
+
+        // Players alternate, so whoever is to move holds
+        // either as many numbers as their opponent or one
+        // fewer, not counting a number given as a handicap.
+        let mut moved = saved.human.clone();
+        if let Some(n) = given {
+            if !moved.remove(n) {
+                return Err("impossible position");
+            }
+        }
+        let (to_move, other) = match mover {
+            Mover::Human => (&moved, &saved.machine),
+            Mover::Machine => (&saved.machine, &moved),
+        };
+        if other.len() < to_move.len() || other.len() > to_move.len() + 1 {
+            return Err("impossible position");
+        }
+        let won = |hand: &Numbers| hand.won(target).is_some();
+        if won(&saved.human) || won(&saved.machine) || saved.board.is_empty() {
+            return Err("game is already over");
+        }
+        Ok(saved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A saved game from digit strings for the pool and the
+    /// two hands.
+    fn game(board: &str, human: &str, machine: &str, mover: Mover) -> SavedGame {
+        let numbers = |digits| Numbers::from_digits(digits).unwrap();
+        SavedGame {
+            board: numbers(board),
+            human: numbers(human),
+            machine: numbers(machine),
+            mover,
+            target: TARGET,
+            given: None,
+        }
+    }
+
+    /// A code made of the given value digits, with the right
+    /// check digit.
+    fn code(digits: &[u32]) -> String {
+        let mut digits = digits.to_vec();
+        digits.push(check_digit(&digits));
+        digits
+            .into_iter()
+            .map(|d| std::char::from_digit(d, 36).unwrap())
+            .collect()
+    }
+
+    /// Decode `code`, expecting it to be rejected as `why`.
+    fn rejected(code: &str, why: &str) {
+        match SavedGame::decode(code) {
+            Ok(_) => panic!("{:?} decoded", code),
+            Err(e) => assert_eq!(e, why, "{:?}", code),
+        }
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        let mut mystery = game("1234689", "7", "5", Mover::Human);
+        mystery.target = 13;
+        let mut given = game("1345789", "62", "", Mover::Machine);
+        given.given = Some(2);
+        let mut both = game("13456789", "2", "", Mover::Human);
+        both.target = 17;
+        both.given = Some(2);
+        let games = [
+            game("123456789", "", "", Mover::Human),
+            game("12346789", "", "5", Mover::Human),
+            game("1346789", "2", "5", Mover::Machine),
+            mystery,
+            given,
+            both,
+        ];
+        for saved in games {
+            let code = saved.encode();
+            let expected_len = match (saved.target, saved.given) {
+                (_, Some(_)) => CODE_LEN + 2,
+                (TARGET, None) => CODE_LEN,
+                _ => CODE_LEN + 1,
+            };
+            assert_eq!(code.len(), expected_len, "{}", code);
+            let decoded = SavedGame::decode(&code).unwrap();
+            assert_eq!(decoded.board, saved.board, "{}", code);
+            assert_eq!(decoded.human, saved.human, "{}", code);
+            assert_eq!(decoded.machine, saved.machine, "{}", code);
+            assert_eq!(decoded.mover, saved.mover, "{}", code);
+            assert_eq!(decoded.target, saved.target, "{}", code);
+            assert_eq!(decoded.given, saved.given, "{}", code);
+        }
+    }
+
+    #[test]
+    fn bad_codes_are_rejected() {
+        let good = game("12346789", "", "5", Mover::Human).encode();
+        let mut typo: Vec<char> = good.chars().collect();
+        typo[3] = if typo[3] == '0' { '1' } else { '0' };
+        rejected(
+            &typo.into_iter().collect::<String>(),
+            "check digit mismatch",
+        );
+        rejected("abc", "malformed code");
+        rejected("abcdefg", "malformed code");
+        rejected("ab!d", "malformed code");
+        let too_big = 2 * POSITIONS;
+        let value = [too_big % 36, too_big / 36 % 36, too_big / 36 / 36];
+        rejected(&code(&value), "no such position");
+        rejected(&code(&[0, 0, 0, TARGET as u32]), "no such target");
+        rejected(&code(&[0, 0, 0, 30]), "no such target");
+        rejected(&code(&[0, 0, 0, TARGET as u32, 0]), "no such handicap");
+    }
+
+    #[test]
+    fn impossible_games_are_rejected() {
+        // The human holds 2, but was given 7.
+        let mut given = game("1345689", "2", "", Mover::Machine);
+        given.given = Some(7);
+        rejected(&given.encode(), "impossible position");
+        // The human took two numbers in a row.
+        rejected(
+            &game("3456789", "12", "", Mover::Machine).encode(),
+            "impossible position",
+        );
+        rejected(
+            &game("1234589", "", "67", Mover::Human).encode(),
+            "impossible position",
+        );
+        // The machine already made 15.
+        rejected(
+            &game("1378", "29", "456", Mover::Human).encode(),
+            "game is already over",
+        );
+        // Nothing left to take.
+        rejected(
+            &game("", "12345", "6789", Mover::Machine).encode(),
+            "game is already over",
+        );
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Games in progress, kept across a server restart.
+//!
+//! The server follows the position of every game in progress
+//! on the event bus. When it is told to shut down it writes
+//! them out, one line per game holding the game id and the
+//! game's save code. The next server reads them back, and a
+//! player picks up where they were with `resume <game>`.
+
+use crate::events::{GameEvent, Subscription};
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Error, ErrorKind};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Games saved by the last server, waiting to be resumed,
+/// as game ids and save codes.
+static RESTORED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Read the games saved at the last shutdown, if any. The
+/// file is removed once read, so each game can only be
+/// resumed once.
+pub fn restore(path: &Path) -> Result<usize, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut restored = RESTORED.lock().unwrap();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(game), Some(code), None) => restored.push((game.to_string(), code.to_string())),
+            _ => return Err(Error::new(ErrorKind::InvalidData, "bad game line")),
+        }
+    }
+    fs::remove_file(path)?;
+    Ok(restored.len())
+}
+
+/// Take the save code for a game from the last server, if
+/// it is still waiting.
+pub fn resume(game: &str) -> Option<String> {
+    let mut restored = RESTORED.lock().unwrap();
+    let i = restored.iter().position(|(g, _)| g == game)?;
+    Some(restored.swap_remove(i).1)
+}
+
+/// Positions of the games in progress, by game id.
+#[derive(Default)]
+pub struct InProgress {
+    games: Mutex<HashMap<String, String>>,
+}
+
+impl InProgress {
+    /// Keep track of games from the event bus until it
+    /// gives up on us.
+    pub fn follow(&self, events: Subscription) {
+        while let Some(event) = events.recv() {
+            let mut games = self.games.lock().unwrap();
+            match event {
+                GameEvent::Position { game, code } => {
+                    games.insert(game, code);
+                }
+                GameEvent::Finished { game, .. } | GameEvent::Abandoned { game, .. } => {
+                    games.remove(&game);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Write the games in progress to `path`, along with any
+    /// from the last server that were never resumed. Returns
+    /// the number of games written.
+    pub fn save(&self, path: &Path) -> Result<usize, Error> {
+        let games = self.games.lock().unwrap();
+        let restored = RESTORED.lock().unwrap();
+        let lines: Vec<String> = games
+            .iter()
+            .chain(restored.iter().map(|(g, c)| (g, c)))
+            .map(|(game, code)| format!("{} {}\n", game, code))
+            .collect();
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, lines.concat())?;
+        fs::rename(tmp, path)?;
+        Ok(lines.len())
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Exhaustive solver for "15". The game tree is tiny, so a
+//! memoized minimax search finds the exact value of any
+//! position in well under a second.
+
+use crate::learning::position;
+use crate::record::Record;
+use crate::Numbers;
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+/// Game-theoretic value of a position for the player about
+/// to move, assuming perfect play on both sides.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Value {
+    Loss,
+    Draw,
+    Win,
+}
+
+impl Value {
+    /// The same position seen from the other side.
+    fn flip(self) -> Value {
+        match self {
+            Value::Loss => Value::Win,
+            Value::Draw => Value::Draw,
+            Value::Win => Value::Loss,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Value::Loss => "loss",
+            Value::Draw => "draw",
+            Value::Win => "win",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Memoized minimax search.
+pub struct Solver {
+    /// The sum to make.
+    target: u64,
+    memo: HashMap<u32, Value>,
+}
+
+impl Solver {
+    /// Create a solver for games played to `target`, with an
+    /// empty memo table.
+    pub fn new(target: u64) -> Solver {
+        Solver {
+            target,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Value of the position for the player holding `own`,
+    /// who is about to pick from `board`.
+    pub fn value(&mut self, board: &Numbers, own: &Numbers, other: &Numbers) -> Value {
+        if other.won(self.target).is_some() {
+            return Value::Loss;
+        }
+        if board.is_empty() {
+            return Value::Draw;
+        }
+        let key = position(own, other);
+        if let Some(&v) = self.memo.get(&key) {
+            return v;
+        }
+        let v = board
+            .iter()
+            .map(|&n| self.move_value(board, own, other, n))
+            .max()
+            .unwrap();
+        self.memo.insert(key, v);
+        v
+    }
+
+    /// Value for the player holding `own` of taking `n`
+    /// from `board`.
+    pub fn move_value(&mut self, board: &Numbers, own: &Numbers, other: &Numbers, n: u64) -> Value {
+        let mut own = own.clone();
+        own.insert(n);
+        if own.won(self.target).is_some() {
+            return Value::Win;
+        }
+        let mut board = board.clone();
+        board.remove(n);
+        self.value(&board, other, &own).flip()
+    }
+
+    /// The value of the position together with every move
+    /// that achieves it, in increasing order.
+    pub fn best_moves(
+        &mut self,
+        board: &Numbers,
+        own: &Numbers,
+        other: &Numbers,
+    ) -> (Value, Vec<u64>) {
+        let mut moves: Vec<(u64, Value)> = board
+            .iter()
+            .map(|&n| (n, self.move_value(board, own, other, n)))
+            .collect();
+        moves.sort();
+        let best = moves.iter().map(|&(_, v)| v).max().unwrap();
+        let moves = moves
+            .into_iter()
+            .filter(|&(_, v)| v == best)
+            .map(|(n, _)| n)
+            .collect();
+        (best, moves)
+    }
+}
+
+/// How a move compares with perfect play.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grade {
+    /// As good as any move available.
+    Best,
+    /// Gives up a win, but still holds the draw.
+    Ok,
+    /// Turns a position that could be held into a loss.
+    Blunder,
+}
+
+impl Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Grade::Best => "best",
+            Grade::Ok => "ok",
+            Grade::Blunder => "blunder",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Solver verdict on one move of a game.
+pub struct Annotation {
+    pub grade: Grade,
+    /// Value of the position after the move, for the player
+    /// who made it.
+    pub value: Value,
+    /// The moves that would have been best.
+    pub best: Vec<u64>,
+}
+
+/// Grade every move of a recorded game.
+pub fn analyze(record: &Record) -> Vec<Annotation> {
+    let mut solver = Solver::new(record.target);
+    let mut hands = record.start.clone();
+    let mut board = Numbers::new();
+    for n in 1..=9 {
+        if !hands[0].contains(n) && !hands[1].contains(n) {
+            board.insert(n);
+        }
+    }
+    let mut annotations = Vec::with_capacity(record.moves.len());
+    for (i, &n) in record.moves.iter().enumerate() {
+        let (own, other) = (&hands[i % 2], &hands[(i + 1) % 2]);
+        let (best_value, best) = solver.best_moves(&board, own, other);
+        let value = solver.move_value(&board, own, other, n);
+        let grade = if value == best_value {
+            Grade::Best
+        } else if value == Value::Loss {
+            Grade::Blunder
+        } else {
+            Grade::Ok
+        };
+        annotations.push(Annotation { grade, value, best });
+        board.remove(n);
+        hands[i % 2].insert(n);
+    }
+    annotations
+}
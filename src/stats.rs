@@ -0,0 +1,101 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Server-wide statistics: how long the server has been up,
+//! who is connected and how many games have been played
+//! today. Days are UTC days.
+
+use crate::events::{GameEvent, Subscription};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Seconds in a day.
+const DAY: u64 = 24 * 60 * 60;
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or(0)
+}
+
+/// Statistics for the whole server.
+pub struct Stats {
+    started: Instant,
+    online: AtomicUsize,
+    /// The current day, and the games finished on it.
+    today: Mutex<(u64, usize)>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats {
+            started: Instant::now(),
+            online: AtomicUsize::new(0),
+            today: Mutex::new((now() / DAY, 0)),
+        }
+    }
+}
+
+/// A connected client, counted as online until dropped.
+pub struct Presence<'a>(&'a Stats);
+
+impl Drop for Presence<'_> {
+    fn drop(&mut self) {
+        self.0.online.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Stats {
+    /// Start counting from now.
+    pub fn new() -> Stats {
+        Stats::default()
+    }
+
+    /// Count games from the event bus as they finish, until
+    /// it gives up on us.
+    pub fn follow(&self, events: Subscription) {
+        while let Some(event) = events.recv() {
+            if let GameEvent::Finished { .. } = event {
+                let mut today = self.today.lock().unwrap();
+                let day = now() / DAY;
+                if today.0 != day {
+                    *today = (day, 0);
+                }
+                today.1 += 1;
+            }
+        }
+    }
+
+    /// Count a client as online for as long as the result
+    /// is kept.
+    pub fn arrive(&self) -> Presence<'_> {
+        self.online.fetch_add(1, Ordering::Relaxed);
+        Presence(self)
+    }
+
+    /// Number of clients connected.
+    pub fn online(&self) -> usize {
+        self.online.load(Ordering::Relaxed)
+    }
+
+    /// Number of games finished so far today.
+    pub fn games_today(&self) -> usize {
+        let today = self.today.lock().unwrap();
+        if today.0 == now() / DAY {
+            today.1
+        } else {
+            0
+        }
+    }
+
+    /// How long the server has been running.
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! What the client's terminal can display.
+//!
+//! Plain telnet gives no reliable way to find out without
+//! negotiating options in-band, so the server asks once when
+//! the client connects. The defaults assume the dumbest
+//! reasonable client, and the player can change their
+//! answer later with the `term` command.
+
+use std::fmt::{self, Display};
+
+/// Capabilities of a client's terminal.
+#[derive(Clone, Copy)]
+pub struct Terminal {
+    /// Can show UTF-8 text beyond ASCII.
+    pub utf8: bool,
+    /// Understands ANSI escape sequences.
+    pub ansi: bool,
+    /// Line width in columns.
+    pub width: usize,
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Terminal {
+            utf8: false,
+            ansi: false,
+            width: 80,
+        }
+    }
+}
+
+impl Display for Terminal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let on_off = |b| if b { "on" } else { "off" };
+        write!(
+            f,
+            "utf8 {}, ansi {}, width {}",
+            on_off(self.utf8),
+            on_off(self.ansi),
+            self.width
+        )
+    }
+}
+
+/// Widest and narrowest line widths a client may ask for.
+const WIDTHS: std::ops::RangeInclusive<usize> = 20..=1000;
+
+/// The question asked when a client connects.
+pub const QUESTION: &str = "terminal (say utf8, ansi and/or a width, or enter for plain): ";
+
+/// Parse an `on` or `off` setting.
+pub fn switch(setting: Option<&str>) -> Result<bool, &'static str> {
+    match setting {
+        Some("on") => Ok(true),
+        Some("off") => Ok(false),
+        _ => Err("expected on or off"),
+    }
+}
+
+impl Terminal {
+    /// Change a setting as asked by a `term` command, given
+    /// the words after `term`.
+    pub fn configure(&mut self, args: &str) -> Result<(), &'static str> {
+        let mut words = args.split_whitespace();
+        match words.next() {
+            Some("utf8") => self.utf8 = switch(words.next())?,
+            Some("ansi") => self.ansi = switch(words.next())?,
+            Some("width") => {
+                let width = words.next().and_then(|w| w.parse::<usize>().ok());
+                match width {
+                    Some(width) if WIDTHS.contains(&width) => self.width = width,
+                    _ => return Err("width must be between 20 and 1000"),
+                }
+            }
+            Some("plain") => *self = Terminal::default(),
+            _ => return Err("settings: utf8 on|off, ansi on|off, width <n>, plain"),
+        }
+        if words.next().is_some() {
+            return Err("too many arguments");
+        }
+        Ok(())
+    }
+
+    /// Set up from the answer to `QUESTION`: any of `utf8`,
+    /// `ansi` and a width, in any order. An empty answer
+    /// keeps the defaults.
+    pub fn answer(&mut self, line: &str) -> Result<(), &'static str> {
+        let mut terminal = Terminal::default();
+        for word in line.split_whitespace() {
+            match word {
+                "utf8" => terminal.utf8 = true,
+                "ansi" => terminal.ansi = true,
+                "plain" => (),
+                _ => match word.parse::<usize>() {
+                    Ok(width) if WIDTHS.contains(&width) => terminal.width = width,
+                    Ok(_) => return Err("width must be between 20 and 1000"),
+                    Err(_) => return Err("expected utf8, ansi or a width"),
+                },
+            }
+        }
+        *self = terminal;
+        Ok(())
+    }
+
+    /// Break the lines of `text` that are wider than the
+    /// terminal at spaces, indenting the pieces like the
+    /// line they came from. A word wider than the terminal
+    /// is left whole.
+    pub fn wrap(&self, text: &str) -> String {
+        let mut wrapped = Vec::new();
+        for line in text.lines() {
+            if line.chars().count() <= self.width {
+                wrapped.push(line.to_string());
+                continue;
+            }
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let mut piece = indent.to_string();
+            for word in line.split_whitespace() {
+                let len = piece.chars().count();
+                if len > indent.len() && len + 1 + word.chars().count() > self.width {
+                    wrapped.push(piece);
+                    piece = indent.to_string();
+                }
+                if piece.len() > indent.len() {
+                    piece.push(' ');
+                }
+                piece.push_str(word);
+            }
+            wrapped.push(piece);
+        }
+        let mut out = wrapped.join("\n");
+        if text.ends_with('\n') {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Emphasize `text`, if the terminal can.
+    pub fn bold(&self, text: &str) -> String {
+        if self.ansi {
+            format!("\x1b[1m{}\x1b[0m", text)
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Byte accounting for client connections.
+//!
+//! A connection's socket is wrapped so that every byte read
+//! or written is counted. The counts go in the access log
+//! and the game log, and a connection may be held to a limit
+//! on the total as a guard against abuse.
+
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Bytes a connection has moved so far.
+pub struct Traffic {
+    read: AtomicU64,
+    written: AtomicU64,
+    /// Most bytes the connection may move in total, if
+    /// limited.
+    limit: Option<u64>,
+}
+
+impl Traffic {
+    /// Start counting from nothing, up to `limit` bytes if
+    /// given.
+    pub fn new(limit: Option<u64>) -> Traffic {
+        Traffic {
+            read: AtomicU64::new(0),
+            written: AtomicU64::new(0),
+            limit,
+        }
+    }
+
+    /// Bytes read from the client.
+    pub fn read(&self) -> u64 {
+        self.read.load(Ordering::Relaxed)
+    }
+
+    /// Bytes written to the client.
+    pub fn written(&self) -> u64 {
+        self.written.load(Ordering::Relaxed)
+    }
+
+    /// Add `n` bytes to `count`, failing once the connection
+    /// is over its limit.
+    fn charge(&self, count: &AtomicU64, n: usize) -> Result<(), io::Error> {
+        count.fetch_add(n as u64, Ordering::Relaxed);
+        match self.limit {
+            Some(limit) if self.read() + self.written() > limit => {
+                Err(io::Error::other("byte limit reached"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A stream whose traffic is counted.
+pub struct Counted<T> {
+    inner: T,
+    traffic: Arc<Traffic>,
+}
+
+impl<T> Counted<T> {
+    /// Count the traffic over `inner` in `traffic`.
+    pub fn new(inner: T, traffic: Arc<Traffic>) -> Counted<T> {
+        Counted { inner, traffic }
+    }
+}
+
+impl<T: Read> Read for Counted<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        let n = self.inner.read(buf)?;
+        self.traffic.charge(&self.traffic.read, n)?;
+        Ok(n)
+    }
+}
+
+impl<T: Write> Write for Counted<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        let n = self.inner.write(buf)?;
+        self.traffic.charge(&self.traffic.written, n)?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        self.inner.flush()
+    }
+}